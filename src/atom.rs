@@ -1,38 +1,48 @@
-use crate::{Context, Input, Inputs, Outputs, Workflow};
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType};
+use crate::{BlockingWorkflow, Context, Input, Inputs, Outputs};
 use anyhow::Result;
 use atom_syndication::Feed;
 use chrono::{Duration, Local};
-use std::{io::BufReader};
+use std::io::BufReader;
 
+#[derive(Clone)]
 pub struct Atom {}
 
 impl Atom {
+    const DESCRIPTION: &'static str = "Parses an Atom feed into one branch per entry.";
+
     // Input
     const TEXT: &'static str = "text";
     const SCHEDULE_IN_SECS: &'static str = "schedule_in_secs";
-    const PARAMS: [&'static str; 2] = [Atom::TEXT, Atom::SCHEDULE_IN_SECS];
+    const PARAMS: [ParameterSpec; 2] = [
+        ParameterSpec::new(Atom::TEXT, ParameterType::String, "Atom document.").required(),
+        ParameterSpec::new(
+            Atom::SCHEDULE_IN_SECS,
+            ParameterType::Int,
+            "Only keep items published within this many seconds.",
+        ),
+    ];
 
     // Output
     const TITLE: &'static str = "title";
     const LINK: &'static str = "link";
-    const OUTPUT: [&'static str; 2] = [Atom::TITLE, Atom::LINK];
+    const OUTPUT: [OutputSpec; 2] = [
+        OutputSpec::new(Atom::TITLE, "Title of the entry."),
+        OutputSpec::new(Atom::LINK, "Address the entry links to."),
+    ];
 }
 
-impl Workflow for Atom {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()> {
+impl BlockingWorkflow for Atom {
+    fn execute(&self, _context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
         let text = input.parameter(Atom::TEXT);
         let after = input
-            .parameter(Atom::SCHEDULE_IN_SECS)
-            .parse()
+            .int(Atom::SCHEDULE_IN_SECS)
             .map(|secs| Local::now() - Duration::seconds(secs));
 
-        let next = match context.next() {
-            Some(next) => next,
-            None => return Ok(()),
-        };
         let feed = Feed::read_from(BufReader::new(text.as_bytes()))?;
+        let mut outputs = Vec::new();
         for entry in feed.entries() {
-            if let Ok(after) = after {
+            if let Some(after) = after {
                 if entry.updated() < &after {
                     break;
                 }
@@ -48,16 +58,19 @@ impl Workflow for Atom {
                     .collect::<Vec<_>>()
                     .join(","),
             );
-            next.execute(context, output)?;
+            outputs.push(output);
         }
 
-        Ok(())
+        Ok(outputs)
     }
 
-    fn parameters(&self) -> &'static [&'static str] {
-        return &Atom::PARAMS;
+    fn description(&self) -> &'static str {
+        Atom::DESCRIPTION
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &Atom::PARAMS
     }
-    fn outputs(&self) -> &'static [&'static str] {
-        return &Atom::OUTPUT;
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &Atom::OUTPUT
     }
 }
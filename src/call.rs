@@ -0,0 +1,238 @@
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType, RawValue, Value};
+use crate::parser::Template;
+use crate::{load_config, Context, Input, Inputs, Outputs, Workflow, ERROR_FIELDS, WORKFLOWS};
+use anyhow::{bail, Context as _, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+pub struct Call {}
+
+impl Call {
+    const DESCRIPTION: &'static str =
+        "Runs another configuration and continues with the outputs its final steps emitted.";
+
+    // Input
+    const CONFIG: &'static str = "config";
+    const JOB: &'static str = "job";
+    const VARS: &'static str = "vars";
+    const EXPORTS: &'static str = "exports";
+    const PARAMS: [ParameterSpec; 4] = [
+        ParameterSpec::new(
+            Call::CONFIG,
+            ParameterType::String,
+            "Configuration to run, relative to this one.",
+        )
+        .required(),
+        ParameterSpec::new(
+            Call::JOB,
+            ParameterType::String,
+            "Job to run when the configuration has several.",
+        ),
+        ParameterSpec::new(
+            Call::VARS,
+            ParameterType::Map,
+            "Values passed in, replacing its vars of the same name.",
+        ),
+        ParameterSpec::new(
+            Call::EXPORTS,
+            ParameterType::List,
+            "Outputs to keep; all of them when left out.",
+        ),
+    ];
+
+    // The outputs are those of the called configuration, or `exports`.
+    const OUTPUT: [OutputSpec; 0] = [];
+}
+
+/// Finds the static name of an output some step type can emit.
+fn known_output(name: &str) -> Option<&'static str> {
+    WORKFLOWS
+        .values()
+        .flat_map(|workflow| workflow.outputs().iter().map(|output| output.name))
+        .chain(ERROR_FIELDS.iter().copied())
+        .find(|output| *output == name)
+}
+
+#[async_trait]
+impl Workflow for Call {
+    async fn execute(&self, context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
+        let path = context.resolve(input.parameter(Call::CONFIG));
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Unable to read {}.", path.display()))?;
+        if context.calls.contains(&canonical) {
+            bail!("{} is already running; calls cannot loop.", path.display());
+        }
+        let config = load_config(&canonical)?;
+        let job = match input.parameter(Call::JOB) {
+            "" => match &config.job_names()?[..] {
+                [job] => job.to_string(),
+                jobs => bail!(
+                    "{} has the jobs {:?}; choose one with `job`.",
+                    path.display(),
+                    jobs
+                ),
+            },
+            job => job.to_string(),
+        };
+        let mut pipeline = config
+            .compile(&job)
+            .with_context(|| format!("Invalid job {} in {}.", job, path.display()))?;
+        for (name, value) in input.map(Call::VARS) {
+            pipeline.set_var(name, value);
+        }
+
+        // Its `file:` secrets and `secret_store` are found next to it.
+        pipeline.path = Some(canonical.clone());
+        // The called pipeline sees neither the caller's steps nor its vars, only its time.
+        let mut called = Context::new(&pipeline)?.limit(context.remaining());
+        called.calls = context.calls.clone();
+        called.calls.push(canonical);
+        let outputs = pipeline
+            .run(&called)
+            .await
+            .with_context(|| format!("{} failed.", path.display()))?;
+
+        let exports = input.list(Call::EXPORTS);
+        if exports.is_empty() {
+            return Ok(outputs);
+        }
+        outputs
+            .iter()
+            .map(|output| {
+                exports
+                    .iter()
+                    .map(|name| match output.get_key_value(&name[..]) {
+                        Some((key, value)) => Ok((*key, value.clone())),
+                        None => bail!("{} did not output `{}`.", path.display(), name),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn description(&self) -> &'static str {
+        Call::DESCRIPTION
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &Call::PARAMS
+    }
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &Call::OUTPUT
+    }
+    fn step_outputs(
+        &self,
+        parameters: &HashMap<String, RawValue<String>>,
+    ) -> Result<Option<Vec<&'static str>>> {
+        let raw = match parameters.get(Call::EXPORTS) {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        let literal = match raw.try_map(|raw| Template::parse(raw))?.as_literal() {
+            Some(literal) => literal,
+            None => return Ok(None),
+        };
+        let names = match Call::PARAMS[3].parse(literal)? {
+            Value::List(names) => names,
+            _ => unreachable!("exports is a list"),
+        };
+        names
+            .iter()
+            .map(|name| {
+                known_output(name)
+                    .with_context(|| format!("No step type outputs `{}` to export.", name))
+            })
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stand_in::TempDir;
+    use crate::{Pipeline, RUNTIME};
+    use std::{fs, path::Path};
+
+    fn call(caller: &Path, parameters: &[(&str, RawValue<String>)]) -> Result<Vec<Outputs>> {
+        let mut input = Inputs::new();
+        for (name, raw) in parameters {
+            let spec = Call::PARAMS.iter().find(|spec| spec.name == *name).unwrap();
+            input.insert(spec.name, spec.parse(raw.clone()).unwrap());
+        }
+        let mut context = Context::new(&Pipeline::default()).unwrap();
+        context.calls.push(caller.to_path_buf());
+        RUNTIME.block_on(Call {}.execute(&context, input))
+    }
+
+    #[test]
+    fn test_call() {
+        let dir = TempDir::new("call");
+        fs::write(dir.join("greeting.txt"), "hello").unwrap();
+        fs::write(
+            dir.join("called.yml"),
+            "vars:\n  file: missing.txt\n\
+             workflows:\n  - type: read\n    parameters:\n      path: \"{vars.file}\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("loop.yml"),
+            "workflows:\n  - type: call\n    parameters:\n      config: loop.yml\n",
+        )
+        .unwrap();
+        let caller = dir.canonicalize().unwrap().join("main.yml");
+        let text = |s: &str| RawValue::Text(s.to_string());
+
+        let file = dir.join("greeting.txt").to_string_lossy().into_owned();
+        let outputs = call(
+            &caller,
+            &[
+                ("config", text("called.yml")),
+                ("vars", RawValue::Map(vec![("file".to_string(), file)])),
+                ("exports", RawValue::List(vec!["text".to_string()])),
+            ],
+        )
+        .unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0]["text"], "hello");
+
+        let error = call(&caller, &[("config", text("called.yml"))]).unwrap_err();
+        assert!(format!("{:#}", error).contains("called.yml failed"));
+
+        let error = call(&caller, &[("config", text("loop.yml"))]).unwrap_err();
+        assert!(format!("{:#}", error).contains("calls cannot loop"));
+
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/token.txt"), "t0ken-from-file\n").unwrap();
+        fs::write(
+            dir.join("sub/keyed.yml"),
+            "secrets:\n  token:\n    file: token.txt\n\
+             workflows:\n  - type: save\n    parameters:\n      \
+             text: \"{secrets.token}\"\n      destination: \"{vars.out}\"\n",
+        )
+        .unwrap();
+        let out = dir.join("token.out").to_string_lossy().into_owned();
+        call(
+            &caller,
+            &[
+                ("config", text("sub/keyed.yml")),
+                ("vars", RawValue::Map(vec![("out".to_string(), out)])),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("token.out")).unwrap(),
+            "t0ken-from-file"
+        );
+
+        let exports = |names: &[&str]| {
+            let mut parameters = HashMap::new();
+            let names = names.iter().map(|name| name.to_string()).collect();
+            parameters.insert(Call::EXPORTS.to_string(), RawValue::List(names));
+            Call {}.step_outputs(&parameters)
+        };
+        assert_eq!(exports(&["text"]).unwrap(), Some(vec!["text"]));
+        assert!(exports(&["texts"]).is_err());
+        assert_eq!(Call {}.step_outputs(&HashMap::new()).unwrap(), None);
+    }
+}
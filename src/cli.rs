@@ -0,0 +1,304 @@
+use crate::logging::Format;
+use anyhow::{anyhow, bail, Context as _, Result};
+
+const USAGE: &str = "Usage: workflows [run] <config> [--job name] [--var name=value]...
+       workflows validate <config>
+       workflows daemon <config>... [--state file]
+       workflows serve <config>... [--listen address]
+       workflows secret <store> <name>
+       workflows list [--json]
+       workflows describe <type> [--json]
+       workflows schema
+Every command also takes -v (repeatable) and --log-format human|json.";
+
+/// Command line options, e.g. `workflows config.yml --var corp_id=ww123`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Subcommand {
+    Run {
+        config: String,
+        /// Runs every job when absent.
+        job: Option<String>,
+        vars: Vec<(String, String)>,
+    },
+    /// Checks the configuration without running it.
+    Validate { config: String },
+    /// Runs the scheduled jobs of the configurations until stopped.
+    Daemon {
+        configs: Vec<String>,
+        /// Where the last run of each job is kept; `daemon::STATE_FILE` when absent.
+        state: Option<String>,
+    },
+    /// Runs the jobs with a webhook trigger when their route is requested.
+    Serve {
+        configs: Vec<String>,
+        /// Such as `0.0.0.0:8080`; `webhook::ADDRESS` when absent.
+        listen: Option<String>,
+    },
+    /// Encrypts what standard input holds into a secret store, under `WORKFLOWS_STORE_KEY`.
+    Secret { store: String, name: String },
+    /// Prints every registered step type.
+    List { json: bool },
+    /// Prints the parameters and outputs of a step type.
+    Describe { workflow_type: String, json: bool },
+    /// Prints a JSON Schema for configuration files.
+    Schema,
+}
+
+/// Options every subcommand takes, e.g. `-vv --log-format json`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Logging {
+    pub verbosity: u8,
+    pub format: Format,
+}
+
+impl Logging {
+    /// Takes the logging options out of `args`, leaving the rest for `Subcommand::parse`.
+    pub fn extract(args: impl IntoIterator<Item = String>) -> Result<(Self, Vec<String>)> {
+        let mut logging = Logging::default();
+        let mut rest = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "--verbose" => logging.verbosity = logging.verbosity.saturating_add(1),
+                _ if arg.len() > 1
+                    && arg[1..].chars().all(|c| c == 'v')
+                    && arg.starts_with('-') =>
+                {
+                    let count = (arg.len() - 1).min(u8::MAX as usize) as u8;
+                    logging.verbosity = logging.verbosity.saturating_add(count);
+                }
+                "--log-format" => {
+                    let format = args.next().context("--log-format expects human or json.")?;
+                    logging.format = parse_format(&format)?;
+                }
+                _ if arg.starts_with("--log-format=") => {
+                    logging.format = parse_format(&arg["--log-format=".len()..])?
+                }
+                _ => rest.push(arg),
+            }
+        }
+        Ok((logging, rest))
+    }
+}
+
+fn parse_format(format: &str) -> Result<Format> {
+    match format {
+        "human" => Ok(Format::Human),
+        "json" => Ok(Format::Json),
+        _ => bail!("--log-format expects human or json, found {}.", format),
+    }
+}
+
+impl Subcommand {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut args = args.into_iter().peekable();
+        let name = match args.peek().map(|arg| &arg[..]) {
+            Some(
+                name @ ("run" | "validate" | "daemon" | "serve" | "secret" | "list" | "describe"
+                | "schema"),
+            ) => Some(name.to_string()),
+            _ => None,
+        };
+        if name.is_some() {
+            args.next();
+        }
+        let mut positionals = Vec::new();
+        let mut vars = Vec::new();
+        let mut job = None;
+        let mut json = false;
+        let mut state = None;
+        let mut listen = None;
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "--var" => {
+                    let var = args.next().context("--var expects name=value.")?;
+                    vars.push(parse_var(&var)?);
+                }
+                _ if arg.starts_with("--var=") => vars.push(parse_var(&arg["--var=".len()..])?),
+                "--job" => job = Some(args.next().context("--job expects a name.")?),
+                _ if arg.starts_with("--job=") => job = Some(arg["--job=".len()..].to_string()),
+                "--json" => json = true,
+                "--state" => state = Some(args.next().context("--state expects a file.")?),
+                _ if arg.starts_with("--state=") => {
+                    state = Some(arg["--state=".len()..].to_string())
+                }
+                "--listen" => listen = Some(args.next().context("--listen expects an address.")?),
+                _ if arg.starts_with("--listen=") => {
+                    listen = Some(arg["--listen=".len()..].to_string())
+                }
+                _ if arg.starts_with('-') => bail!("Unknown option {}.\n{}", arg, USAGE),
+                _ => positionals.push(arg),
+            }
+        }
+
+        let name = name.as_deref().unwrap_or("run");
+        if (!vars.is_empty() || job.is_some()) && name != "run" {
+            bail!("--var and --job only apply to run.\n{}", USAGE);
+        }
+        if json && !matches!(name, "list" | "describe") {
+            bail!("--json only applies to list and describe.\n{}", USAGE);
+        }
+        if state.is_some() && name != "daemon" {
+            bail!("--state only applies to daemon.\n{}", USAGE);
+        }
+        if listen.is_some() && name != "serve" {
+            bail!("--listen only applies to serve.\n{}", USAGE);
+        }
+        let mut positionals = positionals.into_iter();
+        let mut config = || {
+            positionals
+                .next()
+                .with_context(|| format!("No configuration is provided.\n{}", USAGE))
+        };
+        let subcommand = match name {
+            "run" => Subcommand::Run {
+                config: config()?,
+                job,
+                vars,
+            },
+            "validate" => Subcommand::Validate { config: config()? },
+            "daemon" | "serve" => {
+                let configs: Vec<_> = positionals.by_ref().collect();
+                if configs.is_empty() {
+                    bail!("No configuration is provided.\n{}", USAGE);
+                }
+                match name {
+                    "daemon" => Subcommand::Daemon { configs, state },
+                    _ => Subcommand::Serve { configs, listen },
+                }
+            }
+            "secret" => Subcommand::Secret {
+                store: positionals
+                    .next()
+                    .with_context(|| format!("secret expects a store file.\n{}", USAGE))?,
+                name: positionals
+                    .next()
+                    .with_context(|| format!("secret expects a name.\n{}", USAGE))?,
+            },
+            "list" => Subcommand::List { json },
+            "schema" => Subcommand::Schema,
+            _ => Subcommand::Describe {
+                workflow_type: positionals
+                    .next()
+                    .with_context(|| format!("describe expects a step type.\n{}", USAGE))?,
+                json,
+            },
+        };
+        if let Some(arg) = positionals.next() {
+            bail!("Unexpected argument {}.\n{}", arg, USAGE);
+        }
+        Ok(subcommand)
+    }
+}
+
+fn parse_var(var: &str) -> Result<(String, String)> {
+    let mut parts = var.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(anyhow!("--var expects name=value, found {}.", var)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_logging() {
+        let (logging, rest) =
+            Logging::extract(args(&["-vv", "a.yml", "-v", "--log-format", "json"])).unwrap();
+        assert_eq!(
+            logging,
+            Logging {
+                verbosity: 3,
+                format: Format::Json
+            }
+        );
+        assert_eq!(rest, args(&["a.yml"]));
+        assert!(Logging::extract(args(&["--log-format=xml"])).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Subcommand::parse(args(&["a.yml", "--var", "x=1", "--var=y=a=b"])).unwrap(),
+            Subcommand::Run {
+                config: "a.yml".to_string(),
+                job: None,
+                vars: vec![
+                    ("x".to_string(), "1".to_string()),
+                    ("y".to_string(), "a=b".to_string())
+                ],
+            }
+        );
+        assert!(Subcommand::parse(args(&["a.yml", "--var", "x"])).is_err());
+        assert!(Subcommand::parse(args(&["--var", "x=1"])).is_err());
+        assert!(Subcommand::parse(args(&["a.yml", "b.yml"])).is_err());
+
+        assert_eq!(
+            Subcommand::parse(args(&["validate", "a.yml"])).unwrap(),
+            Subcommand::Validate {
+                config: "a.yml".to_string()
+            }
+        );
+        assert_eq!(
+            Subcommand::parse(args(&["run", "a.yml", "--job", "xbox"])).unwrap(),
+            Subcommand::Run {
+                config: "a.yml".to_string(),
+                job: Some("xbox".to_string()),
+                vars: vec![],
+            }
+        );
+        assert!(Subcommand::parse(args(&["run", "a.yml", "--job"])).is_err());
+        assert!(Subcommand::parse(args(&["validate"])).is_err());
+        assert!(Subcommand::parse(args(&["validate", "a.yml", "--var", "x=1"])).is_err());
+
+        assert_eq!(
+            Subcommand::parse(args(&["daemon", "a.yml", "b.yml", "--state=s.json"])).unwrap(),
+            Subcommand::Daemon {
+                configs: args(&["a.yml", "b.yml"]),
+                state: Some("s.json".to_string()),
+            }
+        );
+        assert!(Subcommand::parse(args(&["daemon"])).is_err());
+        assert!(Subcommand::parse(args(&["a.yml", "--state", "s.json"])).is_err());
+        assert_eq!(
+            Subcommand::parse(args(&["serve", "a.yml", "--listen", "0.0.0.0:80"])).unwrap(),
+            Subcommand::Serve {
+                configs: args(&["a.yml"]),
+                listen: Some("0.0.0.0:80".to_string()),
+            }
+        );
+        assert!(Subcommand::parse(args(&["daemon", "a.yml", "--listen=:80"])).is_err());
+        assert_eq!(
+            Subcommand::parse(args(&["secret", "prod.store", "token"])).unwrap(),
+            Subcommand::Secret {
+                store: "prod.store".to_string(),
+                name: "token".to_string(),
+            }
+        );
+        assert!(Subcommand::parse(args(&["secret", "prod.store"])).is_err());
+
+        assert_eq!(
+            Subcommand::parse(args(&["list", "--json"])).unwrap(),
+            Subcommand::List { json: true }
+        );
+        assert_eq!(
+            Subcommand::parse(args(&["describe", "http"])).unwrap(),
+            Subcommand::Describe {
+                workflow_type: "http".to_string(),
+                json: false
+            }
+        );
+        assert!(Subcommand::parse(args(&["describe"])).is_err());
+        assert_eq!(
+            Subcommand::parse(args(&["schema"])).unwrap(),
+            Subcommand::Schema
+        );
+        assert!(Subcommand::parse(args(&["a.yml", "--json"])).is_err());
+    }
+}
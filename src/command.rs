@@ -1,51 +1,181 @@
-use crate::{Context, Input, Inputs, Workflow};
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType};
+use crate::secret;
+use crate::{BlockingWorkflow, Context, Input, Inputs, Outputs};
 use anyhow::Result;
 use std::{
-    collections::HashMap,
-    process::{Command as StdCommand, Stdio},
+    io,
+    process::{Child, Command as StdCommand, Stdio},
+    thread,
+    time::Duration,
 };
 
+#[derive(Clone)]
 pub struct Command {}
 
 impl Command {
+    const DESCRIPTION: &'static str = "Runs a program.";
+
     // Input
     const PROGRAM: &'static str = "program";
     const DAEMON: &'static str = "daemon";
     const INHERIT_IO: &'static str = "inherit_io";
-    const PARAMS: [&'static str; 3] = [Command::PROGRAM, Command::DAEMON, Command::INHERIT_IO];
+    const PARAMS: [ParameterSpec; 3] = [
+        ParameterSpec::new(Command::PROGRAM, ParameterType::String, "Program to run.").required(),
+        ParameterSpec::new(
+            Command::DAEMON,
+            ParameterType::Bool,
+            "Leave the program running instead of waiting for it.",
+        )
+        .default("false"),
+        ParameterSpec::new(
+            Command::INHERIT_IO,
+            ParameterType::Bool,
+            "Show the program's output instead of discarding it.",
+        )
+        .default("false"),
+    ];
+
+    const OUTPUT: [OutputSpec; 0] = [];
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Waits for the program, killing it and anything it started once the deadline passes.
+    fn wait(mut handle: Child, context: &Context) -> Result<()> {
+        if context.remaining().is_none() {
+            handle.wait()?;
+            return Ok(());
+        }
+        while handle.try_wait()?.is_none() {
+            if let Err(timeout) = context.check_deadline() {
+                Command::kill(&mut handle);
+                handle.wait()?;
+                return Err(timeout);
+            }
+            let remaining = context.remaining().unwrap_or_default();
+            thread::sleep(remaining.min(Command::POLL_INTERVAL));
+        }
+        Ok(())
+    }
 
-    const OUTPUT: [&'static str; 0] = [];
+    #[cfg(unix)]
+    fn kill(handle: &mut Child) {
+        // The program leads its own process group, see `execute`.
+        unsafe {
+            libc::kill(-(handle.id() as i32), libc::SIGKILL);
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn kill(handle: &mut Child) {
+        let _ = handle.kill();
+    }
 }
 
-impl Workflow for Command {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()> {
+impl BlockingWorkflow for Command {
+    fn execute(&self, context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
         let program = input.parameter(Command::PROGRAM);
-        let daemon: bool = input.parameter(Command::DAEMON).parse().unwrap_or(false);
-        let inherit_io: bool = input
-            .parameter(Command::INHERIT_IO)
-            .parse()
-            .unwrap_or(false);
+        let daemon = input.flag(Command::DAEMON);
+        let inherit_io = input.flag(Command::INHERIT_IO);
 
         let mut command = StdCommand::new(program);
+        // Shown output passes through here when secrets have to be masked in it.
+        let masked = inherit_io && secret::any();
         if !inherit_io {
             command.stdout(Stdio::null());
             command.stderr(Stdio::null());
+        } else if masked {
+            command.stdout(Stdio::piped());
+            command.stderr(Stdio::piped());
+        }
+        #[cfg(unix)]
+        if !daemon && context.remaining().is_some() {
+            // Lets a timeout kill the children of the program as well.
+            std::os::unix::process::CommandExt::process_group(&mut command, 0);
         }
         let mut handle = command.spawn()?;
+        let mut forwarders = Vec::new();
+        if masked {
+            if let Some(stdout) = handle.stdout.take() {
+                forwarders.push(secret::forward(stdout, io::stdout()));
+            }
+            if let Some(stderr) = handle.stderr.take() {
+                forwarders.push(secret::forward(stderr, io::stderr()));
+            }
+        }
         if !daemon {
-            handle.wait()?;
+            Command::wait(handle, context)?;
+            for forwarder in forwarders {
+                let _ = forwarder.join();
+            }
         }
 
-        if let Some(next) = context.next() {
-            next.execute(context, HashMap::new())?;
-        }
-        Ok(())
+        Ok(vec![Outputs::new()])
     }
 
-    fn parameters(&self) -> &'static [&'static str] {
-        return &Command::PARAMS;
+    fn description(&self) -> &'static str {
+        Command::DESCRIPTION
     }
-    fn outputs(&self) -> &'static [&'static str] {
-        return &Command::OUTPUT;
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &Command::PARAMS
+    }
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &Command::OUTPUT
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use crate::error::TimedOut;
+    use crate::parameter::Value;
+    use crate::stand_in::TempDir;
+    use crate::Pipeline;
+    use std::{fs, os::unix::fs::PermissionsExt, time::Instant};
+
+    #[test]
+    fn test_timeout_kills_program() {
+        let dir = TempDir::new("command");
+        let script = dir.join("timeout.sh");
+        let pid_file = dir.join("background.pid");
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\nsleep 30 &\necho $! > {}\nsleep 30\n",
+                pid_file.display()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let pipeline = Pipeline {
+            timeout: Some(Duration::from_millis(200)),
+            ..Pipeline::default()
+        };
+        let context = Context::new(&pipeline).unwrap();
+        let mut input = Inputs::new();
+        let program = script.to_string_lossy().into_owned();
+        input.insert(Command::PROGRAM, Value::String(program));
+
+        let started = Instant::now();
+        let error = Command {}.execute(&context, input).unwrap_err();
+        assert!(error.is::<TimedOut>());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // The program's own child goes with it; once killed it is gone or a zombie.
+        let pid = fs::read_to_string(&pid_file).unwrap().trim().to_string();
+        let running = || match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Ok(stat) => !stat
+                .rsplit(')')
+                .next()
+                .unwrap()
+                .trim_start()
+                .starts_with('Z'),
+            Err(_) => false,
+        };
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while running() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!running(), "sleep {} outlived the timeout", pid);
     }
 }
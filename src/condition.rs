@@ -0,0 +1,295 @@
+use crate::parser::{argument, complete, expression, Expression, IResult, Namespace, ParseError};
+use crate::{Context, Outputs};
+use anyhow::{Context as _, Result};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, multispace0},
+    combinator::{cut, map, opt},
+    error::context,
+    multi::many0,
+    sequence::{delimited, pair, preceded, terminated},
+};
+use regex::Regex;
+use std::cmp::Ordering;
+
+/// A boolean expression deciding whether a step runs, e.g.
+/// `input.status_code != 200 && !(input.text contains "ok")`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Truthy(Expression),
+    Compare(Expression, Operator, Expression),
+    Not(Box<Condition>),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Contains,
+    Matches,
+}
+
+impl Condition {
+    pub fn parse(raw: &str) -> Result<Self, ParseError> {
+        let condition = complete(
+            raw,
+            delimited(multispace0, or, multispace0),
+            "an operator, `&&`, `||` or the end of the condition",
+        )?;
+        condition.check_patterns(raw)?;
+        Ok(condition)
+    }
+
+    /// Lists every `namespace.field` the condition reads.
+    pub fn variables(&self) -> Vec<(Namespace, &str)> {
+        let mut variables = Vec::new();
+        self.collect_variables(&mut variables);
+        variables
+    }
+
+    fn collect_variables<'a>(&'a self, variables: &mut Vec<(Namespace, &'a str)>) {
+        match self {
+            Condition::Truthy(operand) => operand.collect_variables(variables),
+            Condition::Compare(left, _, right) => {
+                left.collect_variables(variables);
+                right.collect_variables(variables);
+            }
+            Condition::Not(condition) => condition.collect_variables(variables),
+            Condition::And(conditions) | Condition::Or(conditions) => {
+                for condition in conditions {
+                    condition.collect_variables(variables);
+                }
+            }
+        }
+    }
+
+    /// Rejects invalid regular expressions that are known at load time.
+    fn check_patterns(&self, raw: &str) -> Result<(), ParseError> {
+        match self {
+            Condition::Compare(_, Operator::Matches, Expression::Literal(pattern)) => {
+                match Regex::new(pattern) {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(ParseError {
+                        column: raw.find(&pattern[..]).map(|i| i + 1).unwrap_or(1),
+                        expected: "a valid regular expression".to_string(),
+                        found: format!("`{}` ({})", pattern, e),
+                    }),
+                }
+            }
+            Condition::Not(condition) => condition.check_patterns(raw),
+            Condition::And(conditions) | Condition::Or(conditions) => conditions
+                .iter()
+                .try_for_each(|condition| condition.check_patterns(raw)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn evaluate(&self, input: &Outputs, context: &Context) -> Result<bool> {
+        Ok(match self {
+            Condition::Truthy(operand) => truthy(&operand.evaluate(input, context)?),
+            Condition::Compare(left, operator, right) => {
+                let left = left.evaluate(input, context)?;
+                let right = right.evaluate(input, context)?;
+                operator.apply(&left, &right)?
+            }
+            Condition::Not(condition) => !condition.evaluate(input, context)?,
+            Condition::And(conditions) => {
+                for condition in conditions {
+                    if !condition.evaluate(input, context)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            Condition::Or(conditions) => {
+                for condition in conditions {
+                    if condition.evaluate(input, context)? {
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+        })
+    }
+}
+
+impl Operator {
+    fn apply(self, left: &str, right: &str) -> Result<bool> {
+        Ok(match self {
+            Operator::Equal => compare(left, right) == Ordering::Equal,
+            Operator::NotEqual => compare(left, right) != Ordering::Equal,
+            Operator::Less => compare(left, right) == Ordering::Less,
+            Operator::LessOrEqual => compare(left, right) != Ordering::Greater,
+            Operator::Greater => compare(left, right) == Ordering::Greater,
+            Operator::GreaterOrEqual => compare(left, right) != Ordering::Less,
+            Operator::Contains => left.contains(right),
+            Operator::Matches => Regex::new(right)
+                .with_context(|| format!("Invalid regular expression {}.", right))?
+                .is_match(left),
+        })
+    }
+}
+
+/// Compares numerically when both sides are numbers, and as strings otherwise.
+fn compare(left: &str, right: &str) -> Ordering {
+    match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+        (Ok(left), Ok(right)) => left.partial_cmp(&right).unwrap_or(Ordering::Equal),
+        _ => left.cmp(right),
+    }
+}
+
+fn truthy(value: &str) -> bool {
+    !matches!(value.trim(), "" | "0" | "false")
+}
+
+fn operator(input: &str) -> IResult<'_, Operator> {
+    alt((
+        map(tag("=="), |_| Operator::Equal),
+        map(tag("!="), |_| Operator::NotEqual),
+        map(tag("<="), |_| Operator::LessOrEqual),
+        map(tag(">="), |_| Operator::GreaterOrEqual),
+        map(tag("<"), |_| Operator::Less),
+        map(tag(">"), |_| Operator::Greater),
+        map(tag("contains"), |_| Operator::Contains),
+        map(tag("matches"), |_| Operator::Matches),
+    ))(input)
+}
+
+/// An operand is a string, a number, a variable or a function call, optionally
+/// wrapped in braces as it would be in a template.
+fn operand(input: &str) -> IResult<'_, Expression> {
+    alt((
+        delimited(
+            char('{'),
+            preceded(multispace0, cut(expression)),
+            preceded(multispace0, cut(context("a closing brace `}`", char('}')))),
+        ),
+        argument,
+    ))(input)
+}
+
+fn comparison(input: &str) -> IResult<'_, Condition> {
+    let (rest, left) = context("an operand", operand)(input)?;
+    let (rest, operation) = opt(pair(
+        preceded(multispace0, operator),
+        preceded(multispace0, cut(context("an operand", operand))),
+    ))(rest)?;
+    Ok(match operation {
+        Some((operator, right)) => (rest, Condition::Compare(left, operator, right)),
+        None => (rest, Condition::Truthy(left)),
+    })
+}
+
+fn group(input: &str) -> IResult<'_, Condition> {
+    delimited(
+        terminated(char('('), multispace0),
+        cut(or),
+        preceded(
+            multispace0,
+            cut(context("a closing parenthesis `)`", char(')'))),
+        ),
+    )(input)
+}
+
+fn unary(input: &str) -> IResult<'_, Condition> {
+    alt((
+        map(
+            preceded(terminated(char('!'), multispace0), cut(unary)),
+            |condition| Condition::Not(Box::new(condition)),
+        ),
+        group,
+        comparison,
+    ))(input)
+}
+
+/// Parses `item (separator item)*`, folding more than one item with `combine`.
+fn chain<'a>(
+    input: &'a str,
+    separator: &'static str,
+    item: fn(&'a str) -> IResult<'a, Condition>,
+    combine: fn(Vec<Condition>) -> Condition,
+) -> IResult<'a, Condition> {
+    let (rest, (first, others)) = pair(
+        item,
+        many0(preceded(
+            delimited(multispace0, tag(separator), multispace0),
+            cut(context("a condition", item)),
+        )),
+    )(input)?;
+    if others.is_empty() {
+        return Ok((rest, first));
+    }
+    let mut conditions = vec![first];
+    conditions.extend(others);
+    Ok((rest, combine(conditions)))
+}
+
+fn and(input: &str) -> IResult<'_, Condition> {
+    chain(input, "&&", unary, Condition::And)
+}
+
+fn or(input: &str) -> IResult<'_, Condition> {
+    chain(input, "||", and, Condition::Or)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Pipeline;
+
+    fn check(raw: &str, input: &[(&'static str, &str)]) -> bool {
+        let context = Context::new(&Pipeline::default()).unwrap();
+        let input = input.iter().map(|(k, v)| (*k, v.to_string())).collect();
+        Condition::parse(raw)
+            .unwrap()
+            .evaluate(&input, &context)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let input = [("status_code", "500"), ("text", "Service unavailable")];
+        assert!(check("input.status_code != 200", &input));
+        assert!(check("{input.status_code} >= 500", &input));
+        assert!(!check("input.status_code < 20", &input));
+        assert!(check("input.text contains 'unavailable'", &input));
+        assert!(check(r#"input.text matches "^Service \w+$""#, &input));
+        assert!(check(
+            "input.status_code == 200 || !(input.text == '') && input.text",
+            &input
+        ));
+        assert!(!check("!input.text", &input));
+        assert!(!check("'false'", &input));
+        assert!(check("200.0 == 200", &input));
+
+        let input = [("a", "x"), ("b", "x"), ("x", "500")];
+        assert!(check("input.a==input.b", &input));
+        assert!(check("input.x!=200", &input));
+        assert!(check("input.a&&input.b", &input));
+        assert!(check("input.x>=500||input.a<input.b", &input));
+        let condition = Condition::parse("steps.a.text!=200").unwrap();
+        assert_eq!(condition.variables(), vec![(Namespace::Steps, "a.text")]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = Condition::parse("input.status_code = 200").unwrap_err();
+        assert_eq!(error.column, 19);
+
+        let error = Condition::parse("input.text matches '('").unwrap_err();
+        assert_eq!(error.expected, "a valid regular expression");
+
+        let error = Condition::parse("(input.text == 'a'").unwrap_err();
+        assert_eq!(error.expected, "a closing parenthesis `)`");
+
+        assert!(Condition::parse("").is_err());
+        assert!(Condition::parse("input.a &&").is_err());
+    }
+}
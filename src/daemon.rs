@@ -0,0 +1,295 @@
+use crate::trigger::{self, CatchUp};
+use crate::watch::{self, Watcher};
+use crate::{load_config, Context, Outputs, Pipeline};
+use anyhow::{bail, Context as _, Result};
+use chrono::{DateTime, Local, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use futures_util::{
+    future::{self, BoxFuture},
+    FutureExt,
+};
+use std::{
+    collections::BTreeMap,
+    fs, iter,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::{error, info, warn};
+
+/// Where `workflows daemon` keeps the last run of each job unless `--state` says otherwise.
+pub const STATE_FILE: &str = ".workflows-state.json";
+
+/// A job with a `schedule` or `watch` trigger.
+struct Job {
+    /// `<config> job <name>`, as shown in messages.
+    name: String,
+    /// The canonical config path and the job name, as recorded in the state file.
+    key: String,
+    schedule: Option<Schedule>,
+    /// The zone the schedule is read in; local time when absent.
+    timezone: Option<Tz>,
+    catch_up: CatchUp,
+    pipeline: Pipeline,
+    /// Held while the job runs, so its triggers take turns.
+    running: tokio::sync::Mutex<()>,
+}
+
+impl Job {
+    /// The times the job is due after `after`, soonest first.
+    fn due_after(&self, after: DateTime<Utc>) -> Box<dyn Iterator<Item = DateTime<Utc>> + '_> {
+        let schedule = match &self.schedule {
+            Some(schedule) => schedule,
+            None => return Box::new(iter::empty()),
+        };
+        match self.timezone {
+            Some(timezone) => Box::new(
+                schedule
+                    .after(&after.with_timezone(&timezone))
+                    .map(|due| due.with_timezone(&Utc)),
+            ),
+            None => Box::new(
+                schedule
+                    .after(&after.with_timezone(&Local))
+                    .map(|due| due.with_timezone(&Utc)),
+            ),
+        }
+    }
+
+    /// The missed times to run for now, given the last recorded run.
+    fn catch_up(&self, last: DateTime<Utc>, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let missed = self.due_after(last).take_while(|due| *due <= now);
+        match self.catch_up {
+            CatchUp::Skip => Vec::new(),
+            CatchUp::Once => missed.last().into_iter().collect(),
+            CatchUp::All => missed.collect(),
+        }
+    }
+
+    async fn run(&self, input: &Outputs) {
+        let _running = self.running.lock().await;
+        let result = match Context::new(&self.pipeline) {
+            Ok(context) => self.pipeline.run_with_input(&context, input).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            error!("{} failed: {:#}", self.name, error);
+        }
+    }
+
+    async fn run_scheduled(&self, state: &State, due: DateTime<Utc>) {
+        info!("Running {}, due at {}.", self.name, due.to_rfc3339());
+        self.run(&Outputs::new()).await;
+        if let Err(error) = state.record(&self.key, due) {
+            error!("{:#}", error);
+        }
+    }
+
+    /// Runs the job whenever it is due. A run that overlaps later times takes their place,
+    /// so the job never runs twice at once.
+    async fn serve_schedule(&self, state: &State) {
+        if let Some(last) = state.last_run(&self.key) {
+            for due in self.catch_up(last, Utc::now()) {
+                self.run_scheduled(state, due).await;
+            }
+        }
+        loop {
+            let due = match self.due_after(Utc::now()).next() {
+                Some(due) => due,
+                None => {
+                    info!("{} is not due again.", self.name);
+                    return;
+                }
+            };
+            let wait = (due - Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            self.run_scheduled(state, due).await;
+            let skipped = self.due_after(due).take_while(|t| *t <= Utc::now()).count();
+            if skipped > 0 {
+                warn!(
+                    "{} skipped {} run(s) that came due while it ran.",
+                    self.name, skipped
+                );
+            }
+        }
+    }
+
+    /// Runs the job once for each path that changed, with the path and the event as input.
+    async fn serve_watch(&self, mut watcher: Watcher) {
+        while let Some(changes) = watcher.changes().await {
+            for (path, event) in changes {
+                info!(
+                    "Running {}, as {} was {}.",
+                    self.name,
+                    path.display(),
+                    event
+                );
+                let mut input = Outputs::new();
+                input.insert(watch::FIELDS[0], path.display().to_string());
+                input.insert(watch::FIELDS[1], event.to_string());
+                self.run(&input).await;
+            }
+        }
+    }
+}
+
+/// The last run of each job, saved after every run so a restart can catch up.
+struct State {
+    path: PathBuf,
+    runs: Mutex<BTreeMap<String, String>>,
+}
+
+impl State {
+    fn load(path: &Path) -> Result<Self> {
+        let runs = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Invalid state file {}.", path.display()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => {
+                return Err(error).with_context(|| format!("Unable to read {}.", path.display()))
+            }
+        };
+        Ok(State {
+            path: path.to_path_buf(),
+            runs: Mutex::new(runs),
+        })
+    }
+
+    fn last_run(&self, key: &str) -> Option<DateTime<Utc>> {
+        let runs = self.runs.lock().unwrap();
+        let last = DateTime::parse_from_rfc3339(runs.get(key)?).ok()?;
+        Some(last.with_timezone(&Utc))
+    }
+
+    /// Records a run and rewrites the file, replacing it whole so a crash cannot truncate it.
+    fn record(&self, key: &str, due: DateTime<Utc>) -> Result<()> {
+        let mut runs = self.runs.lock().unwrap();
+        runs.insert(key.to_string(), due.to_rfc3339());
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(&*runs)?)
+            .and_then(|_| fs::rename(&temporary, &self.path))
+            .with_context(|| format!("Unable to save {}.", self.path.display()))
+    }
+}
+
+/// Loads the jobs of every configuration that have a schedule or watch files, and runs
+/// them until stopped.
+pub async fn daemon(paths: &[String], state: &Path) -> Result<()> {
+    let mut jobs = Vec::new();
+    let mut watchers = Vec::new();
+    for path in paths {
+        let config = load_config(Path::new(path))?;
+        let canonical = Path::new(path).canonicalize()?;
+        for job in config.job_names()? {
+            let mut pipeline = config
+                .compile(job)
+                .with_context(|| format!("Invalid job {} in {}.", job, path))?;
+            let triggers = pipeline.triggers.clone();
+            if triggers.schedule.is_none() && triggers.watch.is_none() {
+                continue;
+            }
+            let name = format!("{} job {}", path, job);
+            if let Some(watch) = &triggers.watch {
+                let base = canonical.parent().unwrap_or(&canonical);
+                let watcher = Watcher::new(watch, base)
+                    .with_context(|| format!("Unable to watch the files of {}.", name))?;
+                watchers.push((jobs.len(), watcher));
+            }
+            pipeline.path = Some(canonical.clone());
+            jobs.push(Job {
+                name,
+                key: format!("{}#{}", canonical.display(), job),
+                schedule: triggers
+                    .schedule
+                    .as_deref()
+                    .map(trigger::parse_schedule)
+                    .transpose()?,
+                timezone: triggers.compile_timezone()?.or(pipeline.timezone),
+                catch_up: triggers.catch_up,
+                pipeline,
+                running: tokio::sync::Mutex::new(()),
+            });
+        }
+    }
+    if jobs.is_empty() {
+        bail!(
+            "No job in {} has a schedule or watches files.",
+            paths.join(", ")
+        );
+    }
+    let state = State::load(state)?;
+    let mut tasks: Vec<BoxFuture<()>> = Vec::new();
+    for job in jobs.iter().filter(|job| job.schedule.is_some()) {
+        if let Some(due) = job.due_after(Utc::now()).next() {
+            info!("{} is next due at {}.", job.name, due.to_rfc3339());
+        }
+        tasks.push(job.serve_schedule(&state).boxed());
+    }
+    for (index, watcher) in watchers {
+        info!("{} is watching its files.", jobs[index].name);
+        tasks.push(jobs[index].serve_watch(watcher).boxed());
+    }
+    future::join_all(tasks).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stand_in::TempDir;
+
+    fn scheduled(schedule: &str, timezone: Option<Tz>, catch_up: CatchUp) -> Job {
+        Job {
+            name: "test".to_string(),
+            key: "test".to_string(),
+            schedule: Some(trigger::parse_schedule(schedule).unwrap()),
+            timezone,
+            catch_up,
+            pipeline: Pipeline::default(),
+            running: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn time(raw: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(raw)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_catch_up() {
+        let last = time("2021-03-01T10:00:00Z");
+        let now = time("2021-03-01T13:30:00Z");
+        let hourly = |catch_up| scheduled("0 * * * *", Some(Tz::UTC), catch_up);
+        assert!(hourly(CatchUp::Skip).catch_up(last, now).is_empty());
+        assert_eq!(
+            hourly(CatchUp::Once).catch_up(last, now),
+            vec![time("2021-03-01T13:00:00Z")]
+        );
+        assert_eq!(
+            hourly(CatchUp::All).catch_up(last, now),
+            vec![
+                time("2021-03-01T11:00:00Z"),
+                time("2021-03-01T12:00:00Z"),
+                time("2021-03-01T13:00:00Z")
+            ]
+        );
+
+        let morning = scheduled("0 9 * * *", Some(Tz::Asia__Shanghai), CatchUp::Skip);
+        assert_eq!(
+            morning.due_after(last).next(),
+            Some(time("2021-03-02T01:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_state() {
+        let dir = TempDir::new("state");
+        let path = dir.join("state.json");
+        let state = State::load(&path).unwrap();
+        assert_eq!(state.last_run("a"), None);
+        state.record("a", time("2021-03-01T10:00:00Z")).unwrap();
+        let state = State::load(&path).unwrap();
+        assert_eq!(state.last_run("a"), Some(time("2021-03-01T10:00:00Z")));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::WorkflowConfig;
+use anyhow::{bail, Result};
+use std::collections::BTreeSet;
+
+/// The steps one step waits for: those it `needs`, or else the step written before it.
+pub fn dependencies(workflows: &[WorkflowConfig], index: usize) -> Result<Vec<usize>> {
+    let workflow = &workflows[index];
+    let needs = match &workflow.needs {
+        Some(needs) => needs,
+        None => return Ok(index.checked_sub(1).into_iter().collect()),
+    };
+    let mut needed = Vec::new();
+    for id in needs {
+        let found = workflows
+            .iter()
+            .position(|other| other.id.as_ref() == Some(id));
+        match found {
+            Some(found) if found == index => bail!(
+                "Step {} ({}) needs itself.",
+                index + 1,
+                workflow.workflow_type
+            ),
+            Some(found) => needed.push(found),
+            None => bail!(
+                "Step {} ({}) needs `{}`, but no step has that id.",
+                index + 1,
+                workflow.workflow_type,
+                id
+            ),
+        }
+    }
+    Ok(needed)
+}
+
+/// The order to run the steps in, keeping the written order wherever `needs` allows.
+pub fn order(workflows: &[WorkflowConfig]) -> Result<Vec<usize>> {
+    let dependencies = (0..workflows.len())
+        .map(|index| dependencies(workflows, index))
+        .collect::<Result<Vec<_>>>()?;
+    let mut waiting: Vec<usize> = dependencies.iter().map(Vec::len).collect();
+    let mut ready: BTreeSet<usize> = (0..workflows.len())
+        .filter(|index| waiting[*index] == 0)
+        .collect();
+    let mut order = Vec::new();
+    while let Some(index) = ready.pop_first() {
+        order.push(index);
+        for (dependent, needed) in dependencies.iter().enumerate() {
+            for _ in needed.iter().filter(|needed| **needed == index) {
+                waiting[dependent] -= 1;
+                if waiting[dependent] == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+    }
+    if order.len() < workflows.len() {
+        let stuck: Vec<_> = (0..workflows.len())
+            .filter(|index| waiting[*index] > 0)
+            .map(|index| match &workflows[index].id {
+                Some(id) => id.clone(),
+                None => format!("{} ({})", index + 1, workflows[index].workflow_type),
+            })
+            .collect();
+        bail!(
+            "Steps {} depend on each other in a cycle.",
+            stuck.join(", ")
+        );
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workflows(source: &str) -> Vec<WorkflowConfig> {
+        serde_yaml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn test_order() {
+        let chain = workflows("- type: echo\n- type: echo\n- type: echo\n");
+        assert_eq!(order(&chain).unwrap(), vec![0, 1, 2]);
+
+        let joined = workflows(
+            "- type: echo\n  needs: [a, b]\n\
+             - { id: a, type: http, needs: [] }\n\
+             - { id: b, type: http, needs: [] }\n\
+             - type: echo\n",
+        );
+        assert_eq!(order(&joined).unwrap(), vec![1, 2, 0, 3]);
+
+        let missing = workflows("- type: echo\n  needs: [a]\n");
+        let error = order(&missing).unwrap_err().to_string();
+        assert_eq!(error, "Step 1 (echo) needs `a`, but no step has that id.");
+
+        let cycle = workflows(
+            "- { id: a, type: echo, needs: [c] }\n\
+             - { id: b, type: echo }\n\
+             - { id: c, type: echo }\n\
+             - { id: d, type: echo, needs: [] }\n",
+        );
+        let error = order(&cycle).unwrap_err().to_string();
+        assert_eq!(error, "Steps a, b, c depend on each other in a cycle.");
+    }
+}
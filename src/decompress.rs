@@ -1,22 +1,39 @@
-use crate::{Context, Input, Inputs, Workflow};
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType};
+use crate::{BlockingWorkflow, Context, Input, Inputs, Outputs};
 use anyhow::Result;
 use flate2::read::GzDecoder;
-use std::{collections::HashMap, fs::File};
+use std::fs::File;
 use tar::Archive;
 
+#[derive(Clone)]
 pub struct Decompress {}
 
 impl Decompress {
+    const DESCRIPTION: &'static str = "Unpacks a .tar.gz archive.";
+
     // Input
     const PATH: &'static str = "path";
     const DESTINATION: &'static str = "destination";
-    const PARAMS: [&'static str; 2] = [Decompress::PATH, Decompress::DESTINATION];
+    const PARAMS: [ParameterSpec; 2] = [
+        ParameterSpec::new(
+            Decompress::PATH,
+            ParameterType::String,
+            "A .tar.gz archive.",
+        )
+        .required(),
+        ParameterSpec::new(
+            Decompress::DESTINATION,
+            ParameterType::String,
+            "Directory to unpack into.",
+        )
+        .required(),
+    ];
 
-    const OUTPUT: [&'static str; 0] = [];
+    const OUTPUT: [OutputSpec; 0] = [];
 }
 
-impl Workflow for Decompress {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()> {
+impl BlockingWorkflow for Decompress {
+    fn execute(&self, _context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
         let path = input.parameter(Decompress::PATH);
         let destination = input.parameter(Decompress::DESTINATION);
 
@@ -25,16 +42,16 @@ impl Workflow for Decompress {
         let mut archive = Archive::new(tar);
         archive.unpack(destination)?;
 
-        if let Some(next) = context.next() {
-            next.execute(context, HashMap::new())?;
-        }
-        Ok(())
+        Ok(vec![Outputs::new()])
     }
 
-    fn parameters(&self) -> &'static [&'static str] {
-        return &Decompress::PARAMS;
+    fn description(&self) -> &'static str {
+        Decompress::DESCRIPTION
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &Decompress::PARAMS
     }
-    fn outputs(&self) -> &'static [&'static str] {
-        return &Decompress::OUTPUT;
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &Decompress::OUTPUT
     }
 }
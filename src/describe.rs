@@ -0,0 +1,178 @@
+use crate::parameter::{OutputSpec, ParameterSpec};
+use crate::{Workflow, WORKFLOWS};
+use anyhow::{Context as _, Result};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// What `workflows describe <type>` reports about a step type.
+#[derive(Debug, Serialize)]
+struct Description {
+    #[serde(rename = "type")]
+    workflow_type: &'static str,
+    description: &'static str,
+    parameters: &'static [ParameterSpec],
+    outputs: &'static [OutputSpec],
+}
+
+fn describe_type(workflow_type: &'static str) -> Description {
+    let workflow = &WORKFLOWS[workflow_type];
+    Description {
+        workflow_type,
+        description: workflow.description(),
+        parameters: workflow.parameters(),
+        outputs: workflow.outputs(),
+    }
+}
+
+fn sorted_types() -> Vec<&'static str> {
+    let mut types: Vec<_> = WORKFLOWS.keys().copied().collect();
+    types.sort_unstable();
+    types
+}
+
+/// Every registered step type with a one-line summary.
+pub fn list(json: bool) -> Result<String> {
+    let types = sorted_types();
+    if json {
+        #[derive(Serialize)]
+        struct Entry {
+            #[serde(rename = "type")]
+            workflow_type: &'static str,
+            description: &'static str,
+        }
+        let entries: Vec<_> = types
+            .into_iter()
+            .map(|workflow_type| Entry {
+                workflow_type,
+                description: WORKFLOWS[workflow_type].description(),
+            })
+            .collect();
+        return Ok(serde_json::to_string_pretty(&entries)? + "\n");
+    }
+    let width = types.iter().map(|t| t.len()).max().unwrap_or(0);
+    let mut text = String::new();
+    for workflow_type in types {
+        let description = WORKFLOWS[workflow_type].description();
+        writeln!(
+            text,
+            "{:width$}  {}",
+            workflow_type,
+            description,
+            width = width
+        )?;
+    }
+    Ok(text)
+}
+
+/// The parameters and outputs of one step type.
+pub fn describe(workflow_type: &str, json: bool) -> Result<String> {
+    let key = sorted_types()
+        .into_iter()
+        .find(|t| t.eq_ignore_ascii_case(workflow_type))
+        .with_context(|| {
+            format!(
+                "Workflow {} is not found; use one of {:?}.",
+                workflow_type,
+                sorted_types()
+            )
+        })?;
+    let description = describe_type(key);
+    if json {
+        return Ok(serde_json::to_string_pretty(&description)? + "\n");
+    }
+
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{}: {}",
+        description.workflow_type, description.description
+    )?;
+    writeln!(text, "\nParameters:")?;
+    if description.parameters.is_empty() {
+        writeln!(text, "  (none)")?;
+    }
+    let width = description
+        .parameters
+        .iter()
+        .map(|spec| spec.name.len())
+        .max()
+        .unwrap_or(0);
+    for spec in description.parameters {
+        let mut notes = Vec::new();
+        if spec.required {
+            notes.push("required".to_string());
+        }
+        if let Some(default) = spec.default {
+            notes.push(format!("default {}", default));
+        }
+        if spec.secret {
+            notes.push("secret".to_string());
+        }
+        let mut line = format!(
+            "  {:width$}  {:8}  {}",
+            spec.name,
+            spec.kind.to_string(),
+            spec.description,
+            width = width
+        );
+        if !notes.is_empty() {
+            write!(line, " ({})", notes.join(", "))?;
+        }
+        writeln!(text, "{}", line)?;
+    }
+    writeln!(text, "\nOutputs:")?;
+    if description.outputs.is_empty() {
+        writeln!(text, "  (none)")?;
+    }
+    let width = description
+        .outputs
+        .iter()
+        .map(|output| output.name.len())
+        .max()
+        .unwrap_or(0);
+    for output in description.outputs {
+        writeln!(
+            text,
+            "  {:width$}  {}",
+            output.name,
+            output.description,
+            width = width
+        )?;
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let text = describe("HTTP", false).unwrap();
+        assert!(text.starts_with("http: Sends an HTTP request."));
+        assert!(
+            text.contains("  method  string    HTTP method, such as GET or POST. (default GET)")
+        );
+        assert!(text.ends_with(
+            "Outputs:\n  status_code  HTTP status, such as 200.\n  text         Response body.\n"
+        ));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&describe("wechat", true).unwrap()).unwrap();
+        assert_eq!(json["type"], "wechat");
+        assert_eq!(json["parameters"][1]["name"], "secret");
+        assert_eq!(json["parameters"][1]["secret"], true);
+        assert_eq!(json["parameters"][2]["type"], "int");
+        assert_eq!(json["outputs"][0]["name"], "error_code");
+        assert_eq!(
+            json["outputs"][0]["description"],
+            "`errcode` of the reply; 0 on success."
+        );
+
+        assert!(describe("download", false).is_err());
+
+        let json: serde_json::Value = serde_json::from_str(&list(true).unwrap()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), WORKFLOWS.len());
+        assert!(list(false).unwrap().contains("echo        Prints text"));
+    }
+}
@@ -1,33 +1,38 @@
-use crate::{Context, Input, Inputs, Workflow};
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType};
+use crate::secret;
+use crate::{BlockingWorkflow, Context, Input, Inputs, Outputs};
 use anyhow::Result;
-use std::collections::HashMap;
 
+#[derive(Clone)]
 pub struct Echo {}
 
 impl Echo {
+    const DESCRIPTION: &'static str = "Prints text to standard output.";
+
     // Input
     const TEXT: &'static str = "text";
-    const PARAMS: [&'static str; 1] = [Echo::TEXT];
+    const PARAMS: [ParameterSpec; 1] =
+        [ParameterSpec::new(Echo::TEXT, ParameterType::String, "Text to print.").required()];
 
-    const OUTPUT: [&'static str; 0] = [];
+    const OUTPUT: [OutputSpec; 0] = [];
 }
 
-impl Workflow for Echo {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()> {
+impl BlockingWorkflow for Echo {
+    fn execute(&self, _context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
         let text = input.parameter(Echo::TEXT);
 
-        println!("{}", text);
+        println!("{}", secret::mask(text));
 
-        if let Some(next) = context.next() {
-            next.execute(context, HashMap::new())?;
-        }
-        Ok(())
+        Ok(vec![Outputs::new()])
     }
 
-    fn parameters(&self) -> &'static [&'static str] {
-        return &Echo::PARAMS;
+    fn description(&self) -> &'static str {
+        Echo::DESCRIPTION
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &Echo::PARAMS
     }
-    fn outputs(&self) -> &'static [&'static str] {
-        return &Echo::OUTPUT;
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &Echo::OUTPUT
     }
 }
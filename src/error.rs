@@ -0,0 +1,123 @@
+use serde::Deserialize;
+use std::{fmt, time::Duration};
+use strum::{Display, EnumIter};
+
+/// Broad category of a step failure, exposed to `on_failure` handlers as `error.type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, Deserialize, EnumIter)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorKind {
+    /// A parameter template or an `if` condition could not be evaluated.
+    Template,
+    /// The request never produced a response.
+    Network,
+    /// The step or the pipeline ran out of time.
+    Timeout,
+    Io,
+    /// A payload or a parameter was not in the expected format.
+    Parse,
+    Other,
+}
+
+impl ErrorKind {
+    /// Classifies a workflow error by the first recognised cause in its chain.
+    pub fn of(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if cause.is::<TimedOut>() {
+                return ErrorKind::Timeout;
+            }
+            if let Some(error) = cause.downcast_ref::<reqwest::Error>() {
+                return if error.is_timeout() {
+                    ErrorKind::Timeout
+                } else {
+                    ErrorKind::Network
+                };
+            }
+            if cause.is::<std::io::Error>() {
+                return ErrorKind::Io;
+            }
+            if cause.is::<serde_json::Error>()
+                || cause.is::<rss::Error>()
+                || cause.is::<atom_syndication::Error>()
+                || cause.is::<std::num::ParseIntError>()
+                || cause.is::<strum::ParseError>()
+                || cause.is::<url::ParseError>()
+                || cause.is::<http::method::InvalidMethod>()
+            {
+                return ErrorKind::Parse;
+            }
+        }
+        ErrorKind::Other
+    }
+}
+
+/// A failure attributed to one step of a pipeline.
+#[derive(Debug)]
+pub struct StepError {
+    /// The step's id, or its position and type when it has none, e.g. `2 (http)`.
+    pub step: String,
+    pub kind: ErrorKind,
+    pub source: anyhow::Error,
+}
+
+impl StepError {
+    /// The full cause chain on one line.
+    pub fn message(&self) -> String {
+        format!("{:#}", self.source)
+    }
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Step {} failed ({} error).", self.step, self.kind)
+    }
+}
+
+impl std::error::Error for StepError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Raised when a step outlives its own `timeout` or the pipeline's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOut {
+    pub limit: Duration,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Timed out after {:?}.", self.limit)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CLIENT, RUNTIME};
+    use anyhow::{anyhow, Context as _};
+
+    #[test]
+    fn test_of() {
+        let timed_out = anyhow::Error::new(TimedOut {
+            limit: Duration::from_secs(1),
+        });
+        assert_eq!(
+            ErrorKind::of(&timed_out.context("Step 1")),
+            ErrorKind::Timeout
+        );
+        let io = std::io::Error::other("disk");
+        assert_eq!(ErrorKind::of(&anyhow::Error::new(io)), ErrorKind::Io);
+        let json = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        assert_eq!(ErrorKind::of(&json.into()), ErrorKind::Parse);
+        let number = "x".parse::<i64>().context("Invalid agent_id.").unwrap_err();
+        assert_eq!(ErrorKind::of(&number), ErrorKind::Parse);
+        assert_eq!(ErrorKind::of(&anyhow!("Gist not found.")), ErrorKind::Other);
+        let refused = RUNTIME
+            .block_on(CLIENT.get("http://127.0.0.1:1").send())
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&refused.into()), ErrorKind::Network);
+    }
+}
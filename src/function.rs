@@ -0,0 +1,117 @@
+use anyhow::{anyhow, bail, Context as _, Result};
+use chrono::{format::StrftimeItems, Local, Utc};
+use chrono_tz::Tz;
+use rand::Rng;
+use std::fmt::Write;
+use strum::EnumString;
+use uuid::Uuid;
+
+pub const FUNCTIONS: [&str; 5] = ["now", "timestamp", "uuid", "random", "hostname"];
+
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+/// Built-in functions callable from templates, e.g. `{now("%Y-%m-%d")}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Function {
+    Now,
+    Timestamp,
+    Uuid,
+    Random,
+    Hostname,
+}
+
+impl Function {
+    pub fn accepts(self, arguments: usize) -> bool {
+        match self {
+            Function::Now => arguments <= 2,
+            Function::Random => arguments == 2,
+            Function::Timestamp | Function::Uuid | Function::Hostname => arguments == 0,
+        }
+    }
+
+    pub fn usage(self) -> &'static str {
+        match self {
+            Function::Now => "arguments as in `now(format, timezone)`, both optional",
+            Function::Timestamp => "no arguments for `timestamp()`",
+            Function::Uuid => "no arguments for `uuid()`",
+            Function::Random => "arguments as in `random(min, max)`",
+            Function::Hostname => "no arguments for `hostname()`",
+        }
+    }
+
+    pub fn call(self, arguments: &[String], timezone: Option<Tz>) -> Result<String> {
+        match self {
+            Function::Now => {
+                let format = arguments
+                    .first()
+                    .map(|s| &s[..])
+                    .unwrap_or(DEFAULT_TIME_FORMAT);
+                let timezone = match arguments.get(1) {
+                    Some(name) => Some(parse_timezone(name)?),
+                    None => timezone,
+                };
+                now(format, timezone)
+            }
+            Function::Timestamp => Ok(Utc::now().timestamp().to_string()),
+            Function::Uuid => Ok(Uuid::new_v4().to_string()),
+            Function::Random => {
+                let min: i64 = arguments[0]
+                    .parse()
+                    .with_context(|| format!("random: `{}` is not an integer.", arguments[0]))?;
+                let max: i64 = arguments[1]
+                    .parse()
+                    .with_context(|| format!("random: `{}` is not an integer.", arguments[1]))?;
+                if min > max {
+                    bail!("random: {} is greater than {}.", min, max);
+                }
+                Ok(rand::thread_rng().gen_range(min..=max).to_string())
+            }
+            Function::Hostname => Ok(hostname::get()?.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+pub fn parse_timezone(name: &str) -> Result<Tz> {
+    name.parse()
+        .map_err(|_| anyhow!("Unknown timezone {}.", name))
+}
+
+fn now(format: &str, timezone: Option<Tz>) -> Result<String> {
+    let items = StrftimeItems::new(format);
+    let mut result = String::new();
+    let written = match timezone {
+        Some(tz) => write!(
+            result,
+            "{}",
+            Utc::now().with_timezone(&tz).format_with_items(items)
+        ),
+        None => write!(result, "{}", Local::now().format_with_items(items)),
+    };
+    written.map_err(|_| anyhow!("Invalid time format {}.", format))?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call() {
+        let timezone = Some(parse_timezone("UTC").unwrap());
+        let year = Function::Now.call(&["%Y".to_string()], timezone).unwrap();
+        assert_eq!(year.len(), 4);
+        assert!(Function::Now.call(&["%Q".to_string()], timezone).is_err());
+
+        let number = Function::Random
+            .call(&["1".to_string(), "3".to_string()], None)
+            .unwrap();
+        assert!(["1", "2", "3"].contains(&&number[..]));
+        assert!(Function::Random
+            .call(&["3".to_string(), "1".to_string()], None)
+            .is_err());
+
+        assert_eq!(Function::Uuid.call(&[], None).unwrap().len(), 36);
+        assert!(parse_timezone("Mars/Olympus").is_err());
+    }
+}
@@ -1,13 +1,15 @@
-use crate::{Context, Input, Inputs, Workflow, USER_AGENT};
-use anyhow::Result;
-use reqwest::blocking::Client;
-use reqwest::blocking::Response;
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType};
+use crate::{Context, Input, Inputs, Outputs, Workflow, USER_AGENT};
+use anyhow::{Context as _, Result};
+use async_trait::async_trait;
+use reqwest::{Client, Response};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use strum::EnumString;
 
 pub struct Gist {}
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, EnumString)]
 enum GistAction {
     GET,
@@ -26,6 +28,8 @@ struct GistFile<'a> {
 }
 
 impl Gist {
+    const DESCRIPTION: &'static str = "Reads or updates a file in a GitHub gist.";
+
     pub const TEXT: &'static str = "text";
 
     // Input
@@ -33,26 +37,56 @@ impl Gist {
     const GIST_ID: &'static str = "gist_id";
     const ACCESS_TOKEN: &'static str = "access_token";
     const FILE_NAME: &'static str = "file_name";
-    const PARAMS: [&'static str; 5] = [
-        Gist::ACTION,
-        Gist::GIST_ID,
-        Gist::ACCESS_TOKEN,
-        Gist::FILE_NAME,
-        Gist::TEXT,
+    const BASE_URL: &'static str = "base_url";
+    const PARAMS: [ParameterSpec; 6] = [
+        ParameterSpec::new(Gist::ACTION, ParameterType::String, "`get` or `update`.").required(),
+        ParameterSpec::new(Gist::GIST_ID, ParameterType::String, "Id of the gist.").required(),
+        ParameterSpec::new(
+            Gist::ACCESS_TOKEN,
+            ParameterType::String,
+            "GitHub token, needed to update.",
+        )
+        .secret(),
+        ParameterSpec::new(
+            Gist::FILE_NAME,
+            ParameterType::String,
+            "File within the gist.",
+        )
+        .required(),
+        ParameterSpec::new(
+            Gist::TEXT,
+            ParameterType::String,
+            "New content of the file when updating.",
+        ),
+        ParameterSpec::new(
+            Gist::BASE_URL,
+            ParameterType::String,
+            "Root of the GitHub API.",
+        )
+        .default("https://api.github.com"),
     ];
 
     // Output
     const STATUS_CODE: &'static str = "status_code";
-    const OUTPUT: [&'static str; 2] = [Gist::STATUS_CODE, Gist::TEXT];
+    const OUTPUT: [OutputSpec; 2] = [
+        OutputSpec::new(Gist::STATUS_CODE, "HTTP status of the GitHub response."),
+        OutputSpec::new(Gist::TEXT, "Content of the file."),
+    ];
 
-    fn update(gist_id: &str, access_token: &str, file_name: &str, text: &str) -> Result<Response> {
-        let url = format!("https://api.github.com/gists/{}", gist_id);
+    async fn update(
+        client: &Client,
+        base_url: &str,
+        gist_id: &str,
+        access_token: &str,
+        file_name: &str,
+        text: &str,
+    ) -> Result<Response> {
+        let url = format!("{}/gists/{}", base_url.trim_end_matches('/'), gist_id);
 
         let mut files = HashMap::new();
         files.insert(file_name, GistFile { content: text });
 
         let gist_payload = GistPayload { files };
-        let client = Client::new();
 
         Ok(client
             .patch(&url)
@@ -60,52 +94,62 @@ impl Gist {
             .header("Accept", "application/vnd.github.v3+json")
             .header("User-Agent", USER_AGENT)
             .json(&gist_payload)
-            .send()?)
+            .send()
+            .await?)
     }
 
-    fn get(gist_id: &str) -> Result<Response> {
-        let url = format!("https://api.github.com/gists/{}", gist_id);
+    async fn get(client: &Client, base_url: &str, gist_id: &str) -> Result<Response> {
+        let url = format!("{}/gists/{}", base_url.trim_end_matches('/'), gist_id);
 
-        let client = Client::new();
         Ok(client
             .get(&url)
             .header("Accept", "application/vnd.github.v3+json")
             .header("User-Agent", USER_AGENT)
-            .send()?)
+            .send()
+            .await?)
     }
 }
 
+#[async_trait]
 impl Workflow for Gist {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()> {
+    async fn execute(&self, context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
         let action: GistAction = input.parameter(Gist::ACTION).to_uppercase().parse()?;
+        let base_url = input.parameter(Gist::BASE_URL);
         let gist_id = input.parameter(Gist::GIST_ID);
         let access_token = input.parameter(Gist::ACCESS_TOKEN);
         let file_name = input.parameter(Gist::FILE_NAME);
         let text = input.parameter(Gist::TEXT);
 
+        let client = context.http_client();
         let response = match action {
-            GistAction::GET => Gist::get(gist_id),
-            GistAction::UPDATE => Gist::update(gist_id, access_token, file_name, text),
+            GistAction::GET => Gist::get(client, base_url, gist_id).await,
+            GistAction::UPDATE => {
+                Gist::update(client, base_url, gist_id, access_token, file_name, text).await
+            }
         }?;
 
         let mut result = HashMap::new();
         result.insert(Gist::STATUS_CODE, response.status().as_str().to_string());
 
-        let content: String = response.text()?;
+        let content: String = response.text().await?;
         let resp: GistPayload = serde_json::from_str(&content)?;
 
-        result.insert(Gist::TEXT, resp.files[file_name].content.to_string());
+        let file = resp
+            .files
+            .get(file_name)
+            .with_context(|| format!("The gist has no file named {}.", file_name))?;
+        result.insert(Gist::TEXT, file.content.to_string());
 
-        if let Some(next) = context.next() {
-            next.execute(context, result)?;
-        }
-        Ok(())
+        Ok(vec![result])
     }
 
-    fn parameters(&self) -> &'static [&'static str] {
-        return &Gist::PARAMS;
+    fn description(&self) -> &'static str {
+        Gist::DESCRIPTION
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &Gist::PARAMS
     }
-    fn outputs(&self) -> &'static [&'static str] {
-        return &Gist::OUTPUT;
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &Gist::OUTPUT
     }
 }
@@ -1,46 +1,62 @@
-use crate::{Context, Input, Inputs, Workflow};
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType};
+use crate::{Context, Input, Inputs, Outputs, Workflow};
 use anyhow::Result;
-use reqwest::blocking::{Client, Request};
+use async_trait::async_trait;
+use reqwest::Url;
 use std::collections::HashMap;
 
 pub struct Http {}
 
 impl Http {
+    const DESCRIPTION: &'static str = "Sends an HTTP request.";
+
     // Input
     const URL: &'static str = "url";
     const METHOD: &'static str = "method";
-    const PARAMS: [&'static str; 2] = [Http::URL, Http::METHOD];
+    const PARAMS: [ParameterSpec; 2] = [
+        ParameterSpec::new(Http::URL, ParameterType::String, "Address to request.").required(),
+        ParameterSpec::new(
+            Http::METHOD,
+            ParameterType::String,
+            "HTTP method, such as GET or POST.",
+        )
+        .default("GET"),
+    ];
 
     // Output
     const STATUS_CODE: &'static str = "status_code";
     const TEXT: &'static str = "text";
-    const OUTPUT: [&'static str; 2] = [Http::STATUS_CODE, Http::TEXT];
+    const OUTPUT: [OutputSpec; 2] = [
+        OutputSpec::new(Http::STATUS_CODE, "HTTP status, such as 200."),
+        OutputSpec::new(Http::TEXT, "Response body."),
+    ];
 }
 
+#[async_trait]
 impl Workflow for Http {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()> {
+    async fn execute(&self, context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
         let url = input.parameter(Http::URL);
         let method = input.parameter(Http::METHOD);
 
         reqwest::Proxy::all("http://127.0.0.1:7890")?;
-        let client = Client::new();
-        let request = Request::new(method.parse()?, url.parse()?);
-        let response = client.execute(request)?;
+        let client = context.http_client();
+        let request = client.request(method.parse()?, Url::parse(url)?);
+        let response = request.send().await?;
 
         let mut result = HashMap::new();
         result.insert(Http::STATUS_CODE, response.status().as_str().to_string());
-        result.insert(Http::TEXT, response.text()?);
+        result.insert(Http::TEXT, response.text().await?);
 
-        if let Some(next) = context.next() {
-            next.execute(context, result)?;
-        }
-        Ok(())
+        Ok(vec![result])
     }
 
-    fn parameters(&self) -> &'static [&'static str] {
-        return &Http::PARAMS;
+    fn description(&self) -> &'static str {
+        Http::DESCRIPTION
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &Http::PARAMS
     }
-    fn outputs(&self) -> &'static [&'static str] {
-        return &Http::OUTPUT;
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &Http::OUTPUT
     }
 }
@@ -0,0 +1,411 @@
+use anyhow::{bail, Context as _, Result};
+use regex::{Captures, Regex};
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value as Yaml};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A configuration with its `include` and `uses` entries replaced by what they refer to.
+pub struct Expanded {
+    pub value: Yaml,
+    /// Pairs of paths, e.g. `workflows[3]` and `workflows[1]`, from where a step ended up
+    /// to where it was written in the top file; included steps point at their `uses` entry.
+    pub aliases: Vec<(String, String)>,
+}
+
+/// Expands a configuration read from `path`, against which included files are resolved.
+///
+/// At the top level, `include` lists files whose `jobs` are added to the configuration:
+///
+/// ```yaml
+/// include:
+///   - common.yml
+///   - path: notify.yml
+///     with: { channel: ops }
+/// ```
+///
+/// In a list of steps, `uses` replaces the entry with the steps of another file. The file
+/// lists its `steps` and may declare the values it takes under `with`, as defaults or `~`
+/// when they must be passed. `{with.name}` is replaced by the value in every string.
+pub fn expand(source: &str, path: &Path) -> Result<Expanded> {
+    let mut value: Yaml = serde_yaml::from_str(source)?;
+    let mut expander = Expander {
+        stack: vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())],
+        aliases: Vec::new(),
+    };
+    expander.config(&mut value)?;
+    Ok(Expanded {
+        value,
+        aliases: expander.aliases,
+    })
+}
+
+/// Deserializes an expanded configuration. When nothing was included this reads `source`
+/// instead, so errors keep their location.
+pub fn deserialize<T: DeserializeOwned>(source: &str, expanded: Yaml) -> serde_yaml::Result<T> {
+    match serde_yaml::from_str::<Yaml>(source) {
+        Ok(written) if written == expanded => serde_yaml::from_str(source),
+        _ => serde_yaml::from_value(expanded),
+    }
+}
+
+struct Expander {
+    /// The files being expanded, the outermost first.
+    stack: Vec<PathBuf>,
+    aliases: Vec<(String, String)>,
+}
+
+impl Expander {
+    fn dir(&self) -> PathBuf {
+        let file = self
+            .stack
+            .last()
+            .expect("the top file is always on the stack");
+        file.parent().map(Path::to_path_buf).unwrap_or_default()
+    }
+
+    /// Records where a step came from, as long as it is in the top file.
+    fn alias(&mut self, from: String, to: String) {
+        if self.stack.len() == 1 && from != to {
+            self.aliases.push((from, to));
+        }
+    }
+
+    fn config(&mut self, value: &mut Yaml) -> Result<()> {
+        let mapping = match value {
+            Yaml::Mapping(mapping) => mapping,
+            Yaml::Sequence(steps) => return self.steps(steps, ""),
+            _ => return Ok(()),
+        };
+        if let Some(includes) = mapping.remove(&key("include")) {
+            let entries = match includes {
+                Yaml::Sequence(entries) => entries,
+                entry => vec![entry],
+            };
+            for (index, entry) in entries.into_iter().enumerate() {
+                let (file, with) = reference(&entry, "path")
+                    .with_context(|| format!("Invalid include entry {}.", index + 1))?;
+                let included = self.file(&self.dir().join(&file), &with)?;
+                for name in merge(mapping, included, &file)? {
+                    self.alias(format!("jobs.{}", name), format!("include[{}]", index));
+                }
+            }
+        }
+        for section in &["workflows", "steps", "on_failure"] {
+            if let Some(Yaml::Sequence(steps)) = mapping.get_mut(&key(section)) {
+                self.steps(steps, section)?;
+            }
+        }
+        if let Some(Yaml::Mapping(jobs)) = mapping.get_mut(&key("jobs")) {
+            for (name, job) in jobs.iter_mut() {
+                let name = name.as_str().unwrap_or_default();
+                for section in &["steps", "on_failure"] {
+                    if let Some(Yaml::Sequence(steps)) = job.get_mut(section) {
+                        self.steps(steps, &format!("jobs.{}.{}", name, section))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn steps(&mut self, steps: &mut Vec<Yaml>, section: &str) -> Result<()> {
+        let written = std::mem::take(steps);
+        for (index, step) in written.into_iter().enumerate() {
+            let origin = format!("{}[{}]", section, index);
+            if step.get("uses").is_none() {
+                self.alias(format!("{}[{}]", section, steps.len()), origin);
+                steps.push(step);
+                continue;
+            }
+            let (file, with) =
+                reference(&step, "uses").with_context(|| format!("Invalid uses in {}.", origin))?;
+            let included = self
+                .file(&self.dir().join(&file), &with)
+                .with_context(|| format!("Unable to use {} in {}.", file, origin))?;
+            let included = match included {
+                Yaml::Sequence(included) => included,
+                Yaml::Mapping(mut mapping) => match mapping.remove(&key("steps")) {
+                    Some(Yaml::Sequence(included)) => included,
+                    _ => bail!("{} has no steps to use.", file),
+                },
+                _ => bail!("{} has no steps to use.", file),
+            };
+            for step in included {
+                self.alias(format!("{}[{}]", section, steps.len()), origin.clone());
+                steps.push(step);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads an included file, fills in its `with` values and expands it in turn.
+    fn file(&mut self, path: &Path, with: &BTreeMap<String, String>) -> Result<Yaml> {
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Unable to read {}.", path.display()))?;
+        if let Some(start) = self.stack.iter().position(|file| *file == canonical) {
+            let cycle: Vec<_> = self.stack[start..]
+                .iter()
+                .chain(Some(&canonical))
+                .map(|file| file.display().to_string())
+                .collect();
+            bail!("Include cycle: {}.", cycle.join(" -> "));
+        }
+        let source = fs::read_to_string(&canonical)
+            .with_context(|| format!("Unable to read {}.", path.display()))?;
+        let mut value: Yaml = serde_yaml::from_str(&source)
+            .with_context(|| format!("Invalid YAML in {}.", path.display()))?;
+
+        let declared = match value.as_mapping_mut().and_then(|m| m.remove(&key("with"))) {
+            Some(Yaml::Mapping(declared)) => Some(declared),
+            Some(_) => bail!("with in {} should map names to defaults.", path.display()),
+            None => None,
+        };
+        let mut values = BTreeMap::new();
+        if let Some(declared) = &declared {
+            for (name, default) in declared {
+                let name = scalar(name).context("with names should be strings.")?;
+                if !matches!(default, Yaml::Null) {
+                    values.insert(name, scalar(default)?);
+                }
+            }
+        }
+        for (name, value) in with {
+            if let Some(declared) = &declared {
+                if !declared.contains_key(&key(name)) {
+                    bail!("{} does not take with.{}.", path.display(), name);
+                }
+            }
+            values.insert(name.clone(), value.clone());
+        }
+        substitute(&mut value, &values)
+            .with_context(|| format!("Unable to fill in {}.", path.display()))?;
+
+        self.stack.push(canonical);
+        let result = self.config(&mut value);
+        self.stack.pop();
+        result.with_context(|| format!("Unable to expand {}.", path.display()))?;
+        Ok(value)
+    }
+}
+
+fn key(name: &str) -> Yaml {
+    Yaml::String(name.to_string())
+}
+
+fn scalar(value: &Yaml) -> Result<String> {
+    Ok(match value {
+        Yaml::String(text) => text.clone(),
+        Yaml::Number(number) => number.to_string(),
+        Yaml::Bool(flag) => flag.to_string(),
+        _ => bail!("Expected a scalar, found {:?}.", value),
+    })
+}
+
+/// Reads `file_key: path` and the optional `with` map of an `include` or `uses` entry; an
+/// include entry may also be the path alone.
+fn reference(entry: &Yaml, file_key: &str) -> Result<(String, BTreeMap<String, String>)> {
+    if let Yaml::String(file) = entry {
+        return Ok((file.clone(), BTreeMap::new()));
+    }
+    let mapping = entry
+        .as_mapping()
+        .context("Expected a path or a mapping.")?;
+    let mut file = None;
+    let mut with = BTreeMap::new();
+    for (name, value) in mapping {
+        match name.as_str() {
+            Some(name) if name == file_key => file = Some(scalar(value)?),
+            Some("with") => {
+                let values = value.as_mapping().context("with should be a mapping.")?;
+                for (name, value) in values {
+                    with.insert(scalar(name)?, scalar(value)?);
+                }
+            }
+            _ => bail!(
+                "Only {} and with are allowed here, found {:?}.",
+                file_key,
+                name
+            ),
+        }
+    }
+    Ok((
+        file.with_context(|| format!("{} is missing.", file_key))?,
+        with,
+    ))
+}
+
+/// Replaces `{with.name}` in every string of `value`.
+fn substitute(value: &mut Yaml, values: &BTreeMap<String, String>) -> Result<()> {
+    lazy_static::lazy_static! {
+        static ref PLACEHOLDER: Regex = Regex::new(r"\{\s*with\.(\w+)\s*\}").unwrap();
+    }
+    match value {
+        Yaml::String(text) => {
+            let mut missing = None;
+            let replaced = PLACEHOLDER.replace_all(text, |captures: &Captures| {
+                match values.get(&captures[1]) {
+                    Some(value) => value.clone(),
+                    None => {
+                        missing.get_or_insert_with(|| captures[1].to_string());
+                        String::new()
+                    }
+                }
+            });
+            if let Some(name) = missing {
+                bail!("with.{} is neither passed nor given a default.", name);
+            }
+            *text = replaced.into_owned();
+        }
+        Yaml::Sequence(items) => {
+            for item in items {
+                substitute(item, values)?;
+            }
+        }
+        Yaml::Mapping(mapping) => {
+            for (_, item) in mapping.iter_mut() {
+                substitute(item, values)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Adds the jobs of an included configuration, and the vars it has that `config` lacks.
+/// Returns the names of the added jobs.
+fn merge(config: &mut Mapping, included: Yaml, file: &str) -> Result<Vec<String>> {
+    let mut included = match included {
+        Yaml::Mapping(included) => included,
+        _ => bail!("{} should be a configuration with jobs.", file),
+    };
+    if included.contains_key(&key("workflows")) {
+        bail!("{} should list jobs rather than workflows.", file);
+    }
+    let mut added = Vec::new();
+    if let Some(Yaml::Mapping(jobs)) = included.remove(&key("jobs")) {
+        let entry = config
+            .entry(key("jobs"))
+            .or_insert_with(|| Yaml::Mapping(Mapping::new()));
+        let existing = entry
+            .as_mapping_mut()
+            .context("jobs should be a mapping.")?;
+        for (name, job) in jobs {
+            if existing.contains_key(&name) {
+                bail!("Job {} in {} is already defined.", scalar(&name)?, file);
+            }
+            added.push(scalar(&name)?);
+            existing.insert(name, job);
+        }
+    }
+    if let Some(Yaml::Mapping(vars)) = included.remove(&key("vars")) {
+        let entry = config
+            .entry(key("vars"))
+            .or_insert_with(|| Yaml::Mapping(Mapping::new()));
+        let existing = entry
+            .as_mapping_mut()
+            .context("vars should be a mapping.")?;
+        for (name, value) in vars {
+            if !existing.contains_key(&name) {
+                existing.insert(name, value);
+            }
+        }
+    }
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stand_in::TempDir;
+
+    fn load(path: &Path) -> Result<Expanded> {
+        expand(&fs::read_to_string(path).unwrap(), path)
+    }
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = TempDir::new("include");
+        fs::create_dir_all(dir.join("shared")).unwrap();
+        write(
+            &dir,
+            "shared/notify.yml",
+            "with:\n  text: ~\n  agent_id: 1000002\n\
+             steps:\n  - type: echo\n    parameters:\n      text: \"{with.text} ({with.agent_id})\"\n  \
+             - uses: log.yml\n    with: { line: \"{with.text}\" }\n",
+        );
+        write(
+            &dir,
+            "shared/log.yml",
+            "steps:\n  - type: echo\n    parameters:\n      text: \"log {with.line}\"\n",
+        );
+        write(
+            &dir,
+            "jobs.yml",
+            "vars:\n  who: included\n  extra: x\n\
+             jobs:\n  other:\n    steps:\n      - uses: shared/log.yml\n        with: { line: hi }\n",
+        );
+        let main = write(
+            &dir,
+            "main.yml",
+            "include: [jobs.yml]\nvars:\n  who: main\n\
+             workflows:\n  - type: echo\n    parameters:\n      text: first\n  \
+             - uses: shared/notify.yml\n    with:\n      text: \"{input.title}\"\n  \
+             - type: echo\n    parameters:\n      text: last\n",
+        );
+
+        let expanded = load(&main).unwrap();
+        let texts: Vec<_> = expanded.value["workflows"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|step| step["parameters"]["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                "first",
+                "{input.title} (1000002)",
+                "log {input.title}",
+                "last"
+            ]
+        );
+        assert_eq!(
+            expanded.value["jobs"]["other"]["steps"][0]["parameters"]["text"],
+            key("log hi")
+        );
+        assert_eq!(expanded.value["vars"]["who"], key("main"));
+        assert_eq!(expanded.value["vars"]["extra"], key("x"));
+        assert!(expanded
+            .aliases
+            .contains(&("workflows[3]".to_string(), "workflows[2]".to_string())));
+        assert!(expanded
+            .aliases
+            .contains(&("workflows[2]".to_string(), "workflows[1]".to_string())));
+
+        let missing = write(
+            &dir,
+            "missing.yml",
+            "workflows:\n  - uses: shared/notify.yml\n    with: { agent_id: 1 }\n",
+        );
+        let error = format!("{:#}", load(&missing).err().unwrap());
+        assert!(error.contains("with.text is neither passed"), "{}", error);
+
+        write(&dir, "a.yml", "steps:\n  - uses: b.yml\n");
+        write(&dir, "b.yml", "steps:\n  - uses: a.yml\n");
+        let cycle = write(&dir, "cycle.yml", "workflows:\n  - uses: a.yml\n");
+        let error = format!("{:#}", load(&cycle).err().unwrap());
+        assert!(error.contains("Include cycle"), "{}", error);
+        assert!(error.contains("a.yml -> "), "{}", error);
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker, ScanError},
+};
+
+/// Line numbers of the nodes of a YAML document, keyed by paths such as
+/// `workflows[1].parameters.url`. Mapping entries point at the line of their key.
+#[derive(Debug, Default)]
+pub struct Locations {
+    lines: HashMap<String, usize>,
+    aliases: HashMap<String, String>,
+}
+
+impl Locations {
+    pub fn parse(source: &str) -> Result<Self, ScanError> {
+        let mut receiver = Receiver::default();
+        Parser::new(source.chars()).load(&mut receiver, false)?;
+        Ok(Self {
+            lines: receiver.lines,
+            aliases: HashMap::new(),
+        })
+    }
+
+    /// Looks up paths under `from` as if they were under `to`, for nodes that moved when
+    /// the document was expanded.
+    pub fn alias(&mut self, from: String, to: String) {
+        self.aliases.insert(from, to);
+    }
+
+    /// The line of `path`, or of its closest ancestor that appears in the document.
+    pub fn line(&self, path: &str) -> Option<usize> {
+        let moved = self.aliases.iter().find_map(|(from, to)| {
+            let rest = path.strip_prefix(from.as_str())?;
+            (rest.is_empty() || rest.starts_with(['.', '['])).then(|| format!("{}{}", to, rest))
+        });
+        let mut path = moved.as_deref().unwrap_or(path);
+        loop {
+            if let Some(line) = self.lines.get(path) {
+                return Some(*line);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+}
+
+enum Frame {
+    Sequence { path: String, index: usize },
+    Mapping { path: String, key: Option<String> },
+}
+
+#[derive(Default)]
+struct Receiver {
+    stack: Vec<Frame>,
+    lines: HashMap<String, usize>,
+}
+
+impl Receiver {
+    /// Works out the path of the node that starts at `line`, or returns `None` when the
+    /// node is a mapping key, which is remembered for the value that follows it.
+    fn enter(&mut self, scalar: Option<&str>, line: usize) -> Option<String> {
+        let path = match self.stack.last_mut() {
+            None => String::new(),
+            Some(Frame::Sequence { path, index }) => {
+                *index += 1;
+                format!("{}[{}]", path, *index - 1)
+            }
+            Some(Frame::Mapping { path, key }) => match key.take() {
+                Some(key) => join(path, &key),
+                None => {
+                    let name = scalar.unwrap_or_default().to_string();
+                    self.lines.entry(join(path, &name)).or_insert(line);
+                    *key = Some(name);
+                    return None;
+                }
+            },
+        };
+        self.lines.entry(path.clone()).or_insert(line);
+        Some(path)
+    }
+}
+
+impl MarkedEventReceiver for Receiver {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        let line = mark.line();
+        match event {
+            Event::Scalar(value, ..) => {
+                self.enter(Some(&value), line);
+            }
+            Event::Alias(_) => {
+                self.enter(None, line);
+            }
+            Event::SequenceStart(_) => {
+                let path = self.enter(None, line).unwrap_or_default();
+                self.stack.push(Frame::Sequence { path, index: 0 });
+            }
+            Event::MappingStart(_) => {
+                let path = self.enter(None, line).unwrap_or_default();
+                self.stack.push(Frame::Mapping { path, key: None });
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line() {
+        let source = "timezone: UTC\n\
+                      workflows:\n  \
+                        - type: echo\n    \
+                          parameters:\n      \
+                            text: hi\n  \
+                        - type: http\n";
+        let locations = Locations::parse(source).unwrap();
+        assert_eq!(locations.line("timezone"), Some(1));
+        assert_eq!(locations.line("workflows[0]"), Some(3));
+        assert_eq!(locations.line("workflows[0].parameters.text"), Some(5));
+        assert_eq!(locations.line("workflows[1].type"), Some(6));
+        assert_eq!(locations.line("workflows[1].parameters.url"), Some(6));
+        assert_eq!(locations.line("vars.x"), None);
+
+        let mut locations = locations;
+        locations.alias("workflows[2]".to_string(), "workflows[1]".to_string());
+        assert_eq!(locations.line("workflows[2].parameters"), Some(6));
+        assert_eq!(locations.line("workflows[20]"), Some(2));
+    }
+}
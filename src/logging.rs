@@ -0,0 +1,63 @@
+use crate::secret::Masked;
+use std::{
+    env,
+    io::{self, IsTerminal},
+};
+use tracing::Level;
+use tracing_subscriber::EnvFilter;
+
+/// How logs are written to standard error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// One readable line per event, after the spans it happened in.
+    #[default]
+    Human,
+    /// One JSON object per line, with its spans.
+    Json,
+}
+
+/// What is logged by this program: `quiet` and above without `-v`, then one more level
+/// per `-v`. Other crates only log warnings.
+fn directives(verbosity: u8, quiet: Level) -> String {
+    let level = match verbosity {
+        0 => quiet,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    // More verbose levels compare greater.
+    let level = level.max(quiet);
+    format!("warn,workflows={}", level.as_str().to_lowercase())
+}
+
+/// Sends logs to standard error, with secrets masked. `RUST_LOG` takes the place of `-v` when it is set and
+/// `-v` is not given.
+pub fn init(verbosity: u8, format: Format, quiet: Level) {
+    let filter = match env::var(EnvFilter::DEFAULT_ENV) {
+        Ok(directives) if verbosity == 0 && !directives.is_empty() => EnvFilter::new(directives),
+        _ => EnvFilter::new(directives(verbosity, quiet)),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(|| Masked(io::stderr()));
+    match format {
+        Format::Human => builder
+            .with_target(false)
+            .with_ansi(io::stderr().is_terminal())
+            .init(),
+        Format::Json => builder.json().with_span_list(true).init(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directives() {
+        assert_eq!(directives(0, Level::WARN), "warn,workflows=warn");
+        assert_eq!(directives(1, Level::WARN), "warn,workflows=info");
+        assert_eq!(directives(1, Level::DEBUG), "warn,workflows=debug");
+        assert_eq!(directives(5, Level::INFO), "warn,workflows=trace");
+    }
+}
@@ -1,17 +1,39 @@
 mod atom;
+mod call;
+mod cli;
 mod command;
+mod condition;
+mod daemon;
+mod dag;
 mod decompress;
+mod describe;
 mod echo;
+mod error;
+mod function;
 mod gist;
 mod http;
+mod include;
+mod location;
+mod logging;
+mod parameter;
 mod parser;
 mod read;
+mod retry;
 mod rss;
 mod save;
+mod schema;
+mod secret;
+#[cfg(test)]
+mod stand_in;
+mod trigger;
 mod util;
+mod validate;
+mod watch;
+mod webhook;
 mod wechat;
 
 use crate::atom::Atom;
+use crate::call::Call;
 use crate::command::Command;
 use crate::decompress::Decompress;
 use crate::echo::Echo;
@@ -21,59 +43,243 @@ use crate::read::Read;
 use crate::rss::Rss;
 use crate::save::Save;
 use crate::wechat::WeChat;
-use anyhow::{anyhow, Context as _, Result};
+use anyhow::{anyhow, bail, Context as _, Result};
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use condition::Condition;
 use enum_dispatch::enum_dispatch;
+use error::{ErrorKind, StepError, TimedOut};
+use futures_util::{
+    stream::{self, FuturesUnordered},
+    FutureExt, StreamExt, TryStreamExt,
+};
 use lazy_static::lazy_static;
-use parser::fulfill;
+use parameter::{OutputSpec, ParameterSpec, RawValue, Value};
+use parser::{Namespace, Template};
+use reqwest::Client;
+use retry::Retry;
 use serde::Deserialize;
-use std::{collections::HashMap, env, fs};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env, fs,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{runtime::Runtime, sync::Semaphore};
+use tracing::{debug, error, info, info_span, warn, Instrument, Level, Span};
+use trigger::Triggers;
 
-const USER_AGENT: &'static str = "workflows/1.0";
+const USER_AGENT: &str = "workflows/1.0";
+const ERROR_FIELDS: [&str; 3] = ["message", "step", "type"];
+/// The name of the only job of a configuration with a flat `workflows` list.
+const DEFAULT_JOB: &str = "default";
 
+#[async_trait]
 #[enum_dispatch(SupportedWorkflows)]
 trait Workflow {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()>;
-    fn parameters(&self) -> &'static [&'static str];
-    fn outputs(&self) -> &'static [&'static str];
+    /// Runs the step once and returns one set of outputs per branch to continue with.
+    /// Most steps return exactly one; feeds fan out into one branch per item.
+    async fn execute(&self, context: &Context, input: Inputs) -> Result<Vec<Outputs>>;
+    /// One line on what the step does, shown by `workflows list`.
+    fn description(&self) -> &'static str;
+    fn parameters(&self) -> &'static [ParameterSpec];
+    fn outputs(&self) -> &'static [OutputSpec];
+    /// The outputs of a step configured with `parameters`, or `None` when they are only
+    /// known once it runs.
+    fn step_outputs(
+        &self,
+        _parameters: &HashMap<String, RawValue<String>>,
+    ) -> Result<Option<Vec<&'static str>>> {
+        Ok(Some(
+            self.outputs().iter().map(|output| output.name).collect(),
+        ))
+    }
+}
+
+/// A step that does its work synchronously. Registered as `Blocking`, it runs on the
+/// runtime's blocking threads so other branches go on meanwhile.
+trait BlockingWorkflow {
+    fn execute(&self, context: &Context, input: Inputs) -> Result<Vec<Outputs>>;
+    fn description(&self) -> &'static str;
+    fn parameters(&self) -> &'static [ParameterSpec];
+    fn outputs(&self) -> &'static [OutputSpec];
 }
 
-#[derive(Debug)]
+/// Adapts a `BlockingWorkflow` to `Workflow`.
+struct Blocking<T>(T);
+
+#[async_trait]
+impl<T: BlockingWorkflow + Clone + Send + Sync + 'static> Workflow for Blocking<T> {
+    async fn execute(&self, context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
+        let workflow = self.0.clone();
+        let context = context.clone();
+        tokio::task::spawn_blocking(move || BlockingWorkflow::execute(&workflow, &context, input))
+            .await?
+    }
+    fn description(&self) -> &'static str {
+        self.0.description()
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        self.0.parameters()
+    }
+    fn outputs(&self) -> &'static [OutputSpec] {
+        self.0.outputs()
+    }
+}
+
+#[derive(Clone)]
 pub struct Context {
-    state: usize,
-    config: Config,
-    env: HashMap<String, String>,
+    env: Arc<HashMap<String, String>>,
+    timezone: Option<Tz>,
+    vars: Arc<HashMap<String, String>>,
+    secrets: Arc<HashMap<String, String>>,
+    /// Outputs of the steps with an `id` that ran earlier on this branch.
+    steps: HashMap<String, Outputs>,
+    /// `message`, `step` and `type` of the failure an `on_failure` handler is reporting.
+    error: Outputs,
+    /// When the running step has to give up, with the limit that set it.
+    deadline: Option<(Instant, Duration)>,
+    /// Whether steps must emit exactly the outputs they declare.
+    strict: bool,
+    /// The configuration files being run, the outermost first; `call` resolves paths
+    /// against the last one.
+    calls: Vec<PathBuf>,
+    /// How many branches of a fanned-out step run at once, unless the step says otherwise.
+    concurrency: NonZeroUsize,
+    /// Caps how many steps run at once across the pipeline, when it sets `concurrency`.
+    permits: Option<Arc<Semaphore>>,
+    /// Shared by every step, and every context, so connections are pooled.
+    client: Client,
 }
 
 impl Context {
-    fn new(config: Config) -> Self {
+    fn new(pipeline: &Pipeline) -> Result<Self> {
         let env: HashMap<String, String> = env::vars().collect();
+        let base = match pipeline.path.as_deref().and_then(Path::parent) {
+            Some(dir) => dir.to_path_buf(),
+            None => env::current_dir()?,
+        };
+        let secrets = secret::load(&pipeline.secrets, pipeline.secret_store.as_deref(), &base)?;
 
-        Self {
-            state: 0,
-            config,
-            env,
+        let mut context = Self {
+            env: Arc::new(env),
+            timezone: pipeline.timezone,
+            vars: Arc::new(HashMap::new()),
+            secrets: Arc::new(secrets),
+            steps: HashMap::new(),
+            error: Outputs::new(),
+            deadline: pipeline
+                .timeout
+                .map(|timeout| (Instant::now() + timeout, timeout)),
+            strict: pipeline.strict || cfg!(debug_assertions),
+            calls: pipeline.path.iter().cloned().collect(),
+            concurrency: pipeline.concurrency.unwrap_or(NonZeroUsize::MIN),
+            permits: pipeline
+                .concurrency
+                .map(|limit| Arc::new(Semaphore::new(limit.get()))),
+            client: CLIENT.clone(),
+        };
+        let mut vars = HashMap::new();
+        for (name, template) in &pipeline.vars {
+            let value = template
+                .render(&Outputs::new(), &context)
+                .with_context(|| format!("Unable to evaluate vars.{}.", name))?;
+            vars.insert(name.clone(), value);
         }
+        context.vars = Arc::new(vars);
+        Ok(context)
+    }
+
+    /// Looks up `id.output` among the outputs recorded on this branch.
+    fn output(&self, field: &str) -> Option<&String> {
+        let mut parts = field.splitn(2, '.');
+        let (id, output) = (parts.next()?, parts.next()?);
+        self.steps.get(id)?.get(output)
+    }
+
+    /// Time left before the running step has to give up, if it is limited.
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|(deadline, _)| deadline.saturating_duration_since(Instant::now()))
     }
 
-    fn next(&mut self) -> Option<WorkflowConfig> {
-        if self.config.workflows.is_empty() {
-            None
-        } else {
-            Some(self.config.workflows.remove(0))
+    /// Fails once the deadline has passed.
+    fn check_deadline(&self) -> Result<()> {
+        match self.deadline {
+            Some((deadline, limit)) if Instant::now() >= deadline => Err(TimedOut { limit }.into()),
+            _ => Ok(()),
         }
     }
+
+    /// Narrows the deadline to `timeout` from now, unless the current one is sooner.
+    fn limit(&self, timeout: Option<Duration>) -> Context {
+        let mut context = self.clone();
+        if let Some(timeout) = timeout {
+            let deadline = Instant::now() + timeout;
+            if self.deadline.is_none_or(|(current, _)| deadline < current) {
+                context.deadline = Some((deadline, timeout));
+            }
+        }
+        context
+    }
+
+    /// Resolves `path` against the directory of the running configuration.
+    fn resolve(&self, path: &str) -> PathBuf {
+        match self.calls.last().and_then(|config| config.parent()) {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    /// The pooled HTTP client. Requests need no timeout of their own, since steps are
+    /// cancelled when their deadline passes.
+    fn http_client(&self) -> &Client {
+        &self.client
+    }
 }
 
 type Outputs = HashMap<&'static str, String>;
-type Inputs = HashMap<&'static str, String>;
+type Inputs = HashMap<&'static str, Value>;
 
+/// Typed access to parameters, which have been checked against the step's `ParameterSpec`s.
+/// Parameters that were left out read as empty, zero or false.
 trait Input {
     fn parameter(&self, key: &'static str) -> &str;
+    fn int(&self, key: &'static str) -> Option<i64>;
+    fn flag(&self, key: &'static str) -> bool;
+    fn list(&self, key: &'static str) -> &[String];
+    fn map(&self, key: &'static str) -> &[(String, String)];
 }
 
 impl Input for Inputs {
     fn parameter(&self, key: &'static str) -> &str {
-        self.get(key).map(|s| &s[..]).unwrap_or_else(|| "")
+        match self.get(key) {
+            Some(Value::String(s)) => s,
+            _ => "",
+        }
+    }
+    fn int(&self, key: &'static str) -> Option<i64> {
+        match self.get(key) {
+            Some(Value::Int(n)) => Some(*n),
+            _ => None,
+        }
+    }
+    fn flag(&self, key: &'static str) -> bool {
+        matches!(self.get(key), Some(Value::Bool(true)))
+    }
+    fn list(&self, key: &'static str) -> &[String] {
+        match self.get(key) {
+            Some(Value::List(items)) => items,
+            _ => &[],
+        }
+    }
+    fn map(&self, key: &'static str) -> &[(String, String)] {
+        match self.get(key) {
+            Some(Value::Map(entries)) => entries,
+            _ => &[],
+        }
     }
 }
 
@@ -81,84 +287,1603 @@ impl Input for Inputs {
 enum SupportedWorkflows {
     Http,
     Gist,
-    Echo,
+    Echo(Blocking<Echo>),
     WeChat,
-    Command,
-    Save,
-    Decompress,
-    Atom,
-    Read,
-    Rss,
+    Command(Blocking<Command>),
+    Save(Blocking<Save>),
+    Decompress(Blocking<Decompress>),
+    Atom(Blocking<Atom>),
+    Read(Blocking<Read>),
+    Rss(Blocking<Rss>),
+    Call,
 }
 
 lazy_static! {
+    /// Runs every pipeline, including those `call` starts.
+    static ref RUNTIME: Runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Unable to start the async runtime.");
+    /// Shared by every context; clones share its connection pool.
+    static ref CLIENT: Client = Client::new();
+
     static ref WORKFLOWS: HashMap<&'static str, SupportedWorkflows> = {
         let mut m = HashMap::new();
         m.insert("http", Http {}.into());
-        m.insert("echo", Echo {}.into());
+        m.insert("echo", Blocking(Echo {}).into());
         m.insert("wechat", WeChat {}.into());
         m.insert("gist", Gist {}.into());
-        m.insert("command", Command {}.into());
-        m.insert("save", Save {}.into());
-        m.insert("decompress", Decompress {}.into());
-        m.insert("atom", Atom {}.into());
-        m.insert("read", Read {}.into());
-        m.insert("rss", Rss {}.into());
+        m.insert("command", Blocking(Command {}).into());
+        m.insert("save", Blocking(Save {}).into());
+        m.insert("decompress", Blocking(Decompress {}).into());
+        m.insert("atom", Blocking(Atom {}).into());
+        m.insert("read", Blocking(Read {}).into());
+        m.insert("rss", Blocking(Rss {}).into());
+        m.insert("call", Call {}.into());
         m
     };
 }
 
 #[derive(Debug, Deserialize)]
 struct Config {
+    /// The steps of a configuration with a single job.
+    #[serde(default)]
     workflows: Vec<WorkflowConfig>,
+    /// Named pipelines, which take the settings below as defaults.
+    #[serde(default)]
+    jobs: BTreeMap<String, JobConfig>,
+    /// An IANA name such as `Asia/Shanghai`; time functions use local time when absent.
+    timezone: Option<String>,
+    /// Values shared by every step as `{vars.name}`, themselves templated from `env`.
+    #[serde(default)]
+    vars: HashMap<String, String>,
+    /// Values shared by every step as `{secrets.name}`, masked wherever they are printed.
+    #[serde(default)]
+    secrets: BTreeMap<String, secret::Source>,
+    /// The encrypted file `store` secrets are read from, relative to the configuration.
+    secret_store: Option<String>,
+    /// Steps run when the pipeline fails, with `error.message`, `error.step` and `error.type`.
+    #[serde(default)]
+    on_failure: Vec<WorkflowConfig>,
+    /// Limits the whole run; the `on_failure` steps are not counted.
+    #[serde(default, deserialize_with = "util::deserialize_optional_duration")]
+    timeout: Option<Duration>,
+    /// Fails steps that emit other outputs than they declare; always on in debug builds.
+    #[serde(default)]
+    strict: bool,
+    /// How many branches of a fanned-out step run at once; one by default.
+    concurrency: Option<NonZeroUsize>,
+    /// Starts the `workflows` list; each job has its own instead.
+    triggers: Option<Triggers>,
 }
+
+/// One named pipeline in `jobs`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobConfig {
+    steps: Vec<WorkflowConfig>,
+    /// Added to the shared vars, replacing those with the same name.
+    #[serde(default)]
+    vars: HashMap<String, String>,
+    #[serde(default)]
+    triggers: Triggers,
+    /// Replaces the shared handler.
+    on_failure: Option<Vec<WorkflowConfig>>,
+    #[serde(default, deserialize_with = "util::deserialize_optional_duration")]
+    timeout: Option<Duration>,
+    concurrency: Option<NonZeroUsize>,
+}
+
+impl Config {
+    /// The names of the jobs, in the order `workflows run` runs them.
+    fn job_names(&self) -> Result<Vec<&str>> {
+        match (self.workflows.is_empty(), self.jobs.is_empty()) {
+            (false, false) => bail!("Use either workflows or jobs, not both."),
+            (true, true) => bail!("The configuration has neither workflows nor jobs."),
+            (true, false) if self.triggers.is_some() => {
+                bail!("Top-level triggers only apply to workflows; give each job its own.")
+            }
+            (false, true) => Ok(vec![DEFAULT_JOB]),
+            (true, false) => Ok(self.jobs.keys().map(|name| &name[..]).collect()),
+        }
+    }
+
+    fn compile(&self, job: &str) -> Result<Pipeline> {
+        let names = self.job_names()?;
+        if !names.contains(&job) {
+            bail!("Job {} is not found; use one of {:?}.", job, names);
+        }
+        let name = job.to_string();
+        let job = self.jobs.get(job);
+        let steps = match job {
+            Some(job) => &job.steps[..],
+            None => &self.workflows[..],
+        };
+        let steps = compile_steps(steps, false)?;
+        let on_failure = job
+            .and_then(|job| job.on_failure.as_deref())
+            .unwrap_or(&self.on_failure);
+        let on_failure = compile_steps(on_failure, true).context("Invalid on_failure handler.")?;
+        let mut vars = HashMap::new();
+        let job_vars = job.into_iter().flat_map(|job| &job.vars);
+        for (name, value) in self.vars.iter().chain(job_vars) {
+            vars.insert(name.clone(), compile_var(name, value)?);
+        }
+        let triggers = match job {
+            Some(job) => job.triggers.clone(),
+            None => self.triggers.clone().unwrap_or_default(),
+        };
+        triggers.validate()?;
+        secret::check(&self.secrets, self.secret_store.as_deref())?;
+        Ok(Pipeline {
+            name,
+            steps,
+            timezone: self.compile_timezone()?,
+            vars,
+            secrets: self.secrets.clone(),
+            secret_store: self.secret_store.clone(),
+            on_failure,
+            timeout: job.and_then(|job| job.timeout).or(self.timeout),
+            strict: self.strict,
+            path: None,
+            concurrency: job.and_then(|job| job.concurrency).or(self.concurrency),
+            triggers,
+        })
+    }
+
+    fn compile_timezone(&self) -> Result<Option<Tz>> {
+        self.timezone
+            .as_deref()
+            .map(function::parse_timezone)
+            .transpose()
+    }
+}
+
+fn compile_var(name: &str, value: &str) -> Result<Template> {
+    let template =
+        Template::parse(value).with_context(|| format!("Invalid template in vars.{}.", name))?;
+    let namespaces = [Namespace::Input, Namespace::Vars, Namespace::Secrets];
+    if namespaces.iter().any(|ns| template.references(*ns)) {
+        bail!("vars.{} can only use env and functions.", name);
+    }
+    Ok(template)
+}
+
+/// Compiles the steps in the order they will run.
+fn compile_steps(workflows: &[WorkflowConfig], handler: bool) -> Result<Vec<Step>> {
+    let mut steps: Vec<Step> = Vec::new();
+    for index in dag::order(workflows)? {
+        let dependencies = dag::dependencies(workflows, index)?;
+        let step = workflows[index].compile(index, dependencies, &steps, handler)?;
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+/// A configuration whose steps are ready to run.
+#[derive(Default)]
+pub struct Pipeline {
+    /// The job, as logs name it.
+    name: String,
+    steps: Vec<Step>,
+    timezone: Option<Tz>,
+    vars: HashMap<String, Template>,
+    secrets: BTreeMap<String, secret::Source>,
+    secret_store: Option<String>,
+    on_failure: Vec<Step>,
+    timeout: Option<Duration>,
+    strict: bool,
+    /// The file the configuration was read from.
+    path: Option<PathBuf>,
+    concurrency: Option<NonZeroUsize>,
+    triggers: Triggers,
+}
+
+impl Pipeline {
+    /// Replaces a var with a literal value, as given by `--var name=value`.
+    fn set_var(&mut self, name: &str, value: &str) {
+        self.vars.insert(name.to_string(), Template::literal(value));
+    }
+
+    /// Runs the steps and returns the outputs each branch ended with.
+    async fn run(&self, context: &Context) -> Result<Vec<Outputs>> {
+        self.run_with_input(context, &Outputs::new()).await
+    }
+
+    /// Runs the steps with `input` given to the first one, as a trigger provides it.
+    async fn run_with_input(&self, context: &Context, input: &Outputs) -> Result<Vec<Outputs>> {
+        let span = info_span!("job", job = %self.name);
+        self.run_and_handle(context, input).instrument(span).await
+    }
+
+    async fn run_and_handle(&self, context: &Context, input: &Outputs) -> Result<Vec<Outputs>> {
+        let error = match run_steps(&self.steps, context, input).await {
+            Ok(outputs) => return Ok(outputs),
+            Err(error) => error,
+        };
+        if !self.on_failure.is_empty() {
+            let mut input = Outputs::new();
+            input.insert(ERROR_FIELDS[0], secret::mask(&error.message()).into_owned());
+            input.insert(ERROR_FIELDS[1], error.step.clone());
+            input.insert(ERROR_FIELDS[2], error.kind.to_string());
+            let mut context = context.clone();
+            context.error = input.clone();
+            context.deadline = None;
+            if let Err(handler_error) = run_steps(&self.on_failure, &context, &input).await {
+                error!("The on_failure handler failed as well: {:#}", handler_error);
+            }
+        }
+        Err(error.into())
+    }
+}
+
+/// Where a branch of the run stands after a step: the outputs it carries, which run of each
+/// step it descends from, and what `steps.*` reads on it.
+#[derive(Clone, Default)]
+struct Branch {
+    outputs: Outputs,
+    /// The position of the branch it descends from among each step's branches, by step.
+    lineage: BTreeMap<usize, usize>,
+    steps: HashMap<String, Outputs>,
+    /// How many runs of later steps go at once, set by the step it fanned out from.
+    concurrency: Option<NonZeroUsize>,
+}
+
+impl Branch {
+    /// Joins the outputs of two branches that descend from the same branches of the steps
+    /// they share; later outputs win when both emit the same one.
+    fn join(&self, other: &Branch) -> Option<Branch> {
+        let diverged = other
+            .lineage
+            .iter()
+            .any(|(step, position)| self.lineage.get(step).is_some_and(|own| own != position));
+        if diverged {
+            return None;
+        }
+        let mut joined = self.clone();
+        joined.outputs.extend(
+            other
+                .outputs
+                .iter()
+                .map(|(key, value)| (*key, value.clone())),
+        );
+        joined.lineage.extend(&other.lineage);
+        joined.steps.extend(
+            other
+                .steps
+                .iter()
+                .map(|(id, outputs)| (id.clone(), outputs.clone())),
+        );
+        joined.concurrency = match (self.concurrency, other.concurrency) {
+            (Some(own), Some(theirs)) => Some(own.min(theirs)),
+            (own, theirs) => own.or(theirs),
+        };
+        Some(joined)
+    }
+}
+
+/// Runs every step once its dependencies are done, independent ones at the same time, and
+/// returns the branches the last step ended with.
+async fn run_steps(
+    steps: &[Step],
+    context: &Context,
+    input: &Outputs,
+) -> Result<Vec<Outputs>, StepError> {
+    if steps.is_empty() {
+        return Ok(vec![input.clone()]);
+    }
+    let start = Branch {
+        outputs: input.clone(),
+        steps: context.steps.clone(),
+        ..Branch::default()
+    };
+    let mut done: HashMap<usize, Vec<Branch>> = HashMap::new();
+    let mut started = vec![false; steps.len()];
+    let mut running = FuturesUnordered::new();
+    loop {
+        for (position, step) in steps.iter().enumerate() {
+            let dependencies = step.dependencies.as_deref().unwrap_or_default();
+            if started[position] || !dependencies.iter().all(|index| done.contains_key(index)) {
+                continue;
+            }
+            started[position] = true;
+            // Steps that wait for nothing start from the pipeline's input.
+            let starts = match &step.dependencies {
+                None => vec![start.clone()],
+                Some(dependencies) if dependencies.is_empty() => vec![start.clone()],
+                Some(dependencies) => dependencies.iter().fold(
+                    vec![Branch {
+                        outputs: Outputs::new(),
+                        ..start.clone()
+                    }],
+                    |starts, index| {
+                        let branches = &done[index];
+                        starts
+                            .iter()
+                            .flat_map(|start| branches.iter().filter_map(move |b| start.join(b)))
+                            .collect()
+                    },
+                ),
+            };
+            running.push(run_step(step, context, starts).map(move |result| (position, result)));
+        }
+        let (position, result) = match running.next().await {
+            Some(finished) => finished,
+            None => break,
+        };
+        done.insert(steps[position].index, result?);
+    }
+    let last = steps.last().expect("There is a step.");
+    let ends = done.remove(&last.index).unwrap_or_default();
+    Ok(ends.into_iter().map(|branch| branch.outputs).collect())
+}
+
+/// Runs `step` once for each branch it starts from, as many at once as the step those
+/// branches fanned out from allows. Their branches are kept in order, and the first failure
+/// in that order stops the rest.
+async fn run_step(
+    step: &Step,
+    context: &Context,
+    starts: Vec<Branch>,
+) -> Result<Vec<Branch>, StepError> {
+    let concurrency = starts
+        .iter()
+        .filter_map(|start| start.concurrency)
+        .min()
+        .unwrap_or(context.concurrency)
+        .get();
+    let several = starts.len() > 1;
+    let runs = starts.into_iter().enumerate().map(|(index, start)| {
+        let span = match several {
+            true => info_span!("item", step = %step.name(), index),
+            false => Span::none(),
+        };
+        run_branch(step, context, start).instrument(span)
+    });
+    let branches: Vec<Vec<Branch>> = stream::iter(runs)
+        .buffered(concurrency)
+        .try_collect()
+        .await?;
+    let mut branches: Vec<Branch> = branches.into_iter().flatten().collect();
+    for (position, branch) in branches.iter_mut().enumerate() {
+        branch.lineage.insert(step.index, position);
+    }
+    Ok(branches)
+}
+
+/// Runs `step` on one branch and returns the branches it continues into.
+async fn run_branch(
+    step: &Step,
+    context: &Context,
+    start: Branch,
+) -> Result<Vec<Branch>, StepError> {
+    let mut context = context.clone();
+    context.steps = start.steps.clone();
+    let input = &start.outputs;
+    if let Some(condition) = &step.condition {
+        let holds = condition
+            .evaluate(input, &context)
+            .map_err(|e| step.fail(ErrorKind::Template, e))?;
+        if !holds {
+            return match step.on_false {
+                OnFalse::Continue => Ok(vec![start]),
+                OnFalse::Stop => Ok(Vec::new()),
+            };
+        }
+    }
+    let _permit = match &context.permits {
+        Some(permits) => Some(
+            permits
+                .acquire()
+                .await
+                .expect("The permits are never closed."),
+        ),
+        None => None,
+    };
+    let span = info_span!("step", step = %step.name(), step_type = %step.workflow_type);
+    let started = Instant::now();
+    let result = step.execute(&context, input).instrument(span.clone()).await;
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    span.in_scope(|| match &result {
+        Ok(outputs) => {
+            let bytes: usize = outputs
+                .iter()
+                .flat_map(|o| o.values())
+                .map(String::len)
+                .sum();
+            let branches = outputs.len();
+            info!(elapsed_ms, branches, bytes, "Step finished.");
+        }
+        Err(error) => info!(elapsed_ms, error = %error.message(), "Step failed."),
+    });
+    let outputs = match result {
+        Ok(outputs) => outputs,
+        Err(error) if step.continue_on_error => {
+            warn!("{} Continuing: {}", error, error.message());
+            return Ok(vec![Branch {
+                outputs: Outputs::new(),
+                ..start
+            }]);
+        }
+        Err(error) => return Err(error),
+    };
+    let branches = outputs.into_iter().map(|outputs| {
+        let mut steps = start.steps.clone();
+        if let Some(id) = &step.id {
+            steps.insert(id.clone(), outputs.clone());
+        }
+        Branch {
+            outputs,
+            lineage: start.lineage.clone(),
+            steps,
+            concurrency: step.concurrency.or(start.concurrency),
+        }
+    });
+    Ok(branches.collect())
+}
+
 #[derive(Debug, Deserialize)]
 struct WorkflowConfig {
+    /// Names the step so later steps can read its outputs as `{steps.<id>.<output>}`.
+    id: Option<String>,
     #[serde(rename = "type")]
     workflow_type: String,
-    parameters: HashMap<String, String>,
+    /// Ids of the steps to run first, whose outputs are joined into `input`; `[]` starts
+    /// from the pipeline's input, and without it a step follows the one written before it.
+    needs: Option<Vec<String>>,
+    #[serde(default)]
+    parameters: HashMap<String, RawValue<String>>,
+    /// Runs the step only when the condition holds, e.g. `input.status_code != 200`.
+    #[serde(rename = "if")]
+    condition: Option<String>,
+    #[serde(default)]
+    on_false: OnFalse,
+    /// Lets the rest of the pipeline run, with empty inputs, when this step fails.
+    #[serde(default)]
+    continue_on_error: bool,
+    retry: Option<Retry>,
+    /// Limits each attempt of the step.
+    #[serde(default, deserialize_with = "util::deserialize_optional_duration")]
+    timeout: Option<Duration>,
+    /// How many of the branches this step fans out into run at once.
+    concurrency: Option<NonZeroUsize>,
 }
 
-impl WorkflowConfig {
-    fn execute(&self, context: &mut Context, output: Outputs) -> Result<()> {
-        let input: HashMap<String, String> = output
-            .into_iter()
-            .map(|(k, v)| (k.to_string(), v))
-            .collect();
-        let (workflow, payload) = self.make_workflow(&input, &context)?;
-        workflow.execute(context, payload)
-    }
+/// What happens to a branch when a step's `if` is false.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum OnFalse {
+    /// Skip the step and hand its input to the next one.
+    #[default]
+    Continue,
+    /// End the branch without running the remaining steps.
+    Stop,
+}
 
-    fn make_workflow(
+impl WorkflowConfig {
+    /// Compiles the step written at `index`, given the steps it waits for and those
+    /// compiled before it.
+    fn compile(
         &self,
-        input: &HashMap<String, String>,
-        context: &Context,
-    ) -> Result<(&'static SupportedWorkflows, Inputs)> {
+        index: usize,
+        dependencies: Vec<usize>,
+        previous: &[Step],
+        handler: bool,
+    ) -> Result<Step> {
         let workflow = WORKFLOWS
             .get(&self.workflow_type.to_lowercase()[..])
             .context(anyhow!("Workflow {} is not found.", self.workflow_type))?;
-        let mut payload: HashMap<&'static str, String> = HashMap::new();
-        for key in workflow.parameters() {
-            if let Some(value) = self.parameters.get(*key) {
-                payload.insert(key, fulfill(value, input, &context)?);
+        if let Some(id) = &self.id {
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+            {
+                bail!(
+                    "Step {} ({}) has an invalid id `{}`; use letters, digits, `_` and `-`.",
+                    index + 1,
+                    self.workflow_type,
+                    id
+                );
+            }
+            if previous.iter().any(|step| step.id.as_ref() == Some(id)) {
+                bail!(
+                    "Step {} ({}) reuses the id `{}`.",
+                    index + 1,
+                    self.workflow_type,
+                    id
+                );
             }
         }
-        Ok((workflow, payload))
+        // The first step written without `needs` starts from the pipeline's input.
+        let dependencies = match (&self.needs, dependencies.is_empty()) {
+            (None, true) => None,
+            _ => Some(dependencies),
+        };
+        let visible = ancestors(previous, dependencies.as_deref().unwrap_or_default());
+        let mut parameters = Vec::new();
+        for spec in workflow.parameters() {
+            let value = match (self.parameters.get(spec.name), spec.default) {
+                (Some(value), _) => value.clone(),
+                (None, Some(default)) => RawValue::Text(default.to_string()),
+                (None, None) if spec.required => bail!(
+                    "Step {} ({}) is missing the required parameter `{}`.",
+                    index + 1,
+                    self.workflow_type,
+                    spec.name
+                ),
+                (None, None) => continue,
+            };
+            let describe = || {
+                format!(
+                    "Invalid template in step {} ({}), parameter `{}`.",
+                    index + 1,
+                    self.workflow_type,
+                    spec.name
+                )
+            };
+            let templates = value
+                .try_map(|raw| Template::parse(raw))
+                .with_context(describe)?;
+            for template in templates.items() {
+                check_references(&template.variables(), &visible, handler)
+                    .with_context(describe)?;
+            }
+            // Values without placeholders are checked now rather than when the step runs.
+            if let Some(literal) = templates.as_literal() {
+                spec.parse(literal).with_context(|| {
+                    format!(
+                        "Invalid value in step {} ({}), parameter `{}`.",
+                        index + 1,
+                        self.workflow_type,
+                        spec.name
+                    )
+                })?;
+            }
+            parameters.push((spec, templates));
+        }
+        let condition = match &self.condition {
+            Some(raw) => {
+                let describe =
+                    || format!("Invalid if in step {} ({}).", index + 1, self.workflow_type);
+                let condition = Condition::parse(raw).with_context(describe)?;
+                check_references(&condition.variables(), &visible, handler)
+                    .with_context(describe)?;
+                Some(condition)
+            }
+            None => None,
+        };
+        let outputs = workflow.step_outputs(&self.parameters).with_context(|| {
+            format!(
+                "Invalid outputs of step {} ({}).",
+                index + 1,
+                self.workflow_type
+            )
+        })?;
+        if let Some(retry) = &self.retry {
+            retry.validate().with_context(|| {
+                format!(
+                    "Invalid retry in step {} ({}).",
+                    index + 1,
+                    self.workflow_type
+                )
+            })?;
+        }
+        Ok(Step {
+            id: self.id.clone(),
+            index,
+            workflow_type: self.workflow_type.clone(),
+            workflow,
+            dependencies,
+            parameters,
+            outputs,
+            condition,
+            on_false: self.on_false,
+            continue_on_error: self.continue_on_error,
+            retry: self.retry.clone(),
+            timeout: self.timeout,
+            concurrency: self.concurrency,
+        })
     }
 }
 
-fn main() -> Result<()> {
-    let config_path = env::args()
-        .nth(1)
-        .context("No configuration is provided.")?;
+/// The steps among `previous` that the `dependencies` lead back to, which are the ones whose
+/// outputs a step can read on its branch.
+fn ancestors<'a>(previous: &'a [Step], dependencies: &[usize]) -> Vec<&'a Step> {
+    let mut pending = dependencies.to_vec();
+    let mut found: Vec<&Step> = Vec::new();
+    while let Some(index) = pending.pop() {
+        if found.iter().any(|step| step.index == index) {
+            continue;
+        }
+        if let Some(step) = previous.iter().find(|step| step.index == index) {
+            pending.extend(step.dependencies.iter().flatten());
+            found.push(step);
+        }
+    }
+    found
+}
 
-    let config = fs::read_to_string(config_path)?;
-    let config: Config = serde_yaml::from_str(&config)?;
+/// Ensures every `steps.<id>.<output>` names a step this one depends on and one of its
+/// outputs, and that `error.*` is only read by `on_failure` handlers.
+fn check_references(
+    variables: &[(Namespace, &str)],
+    visible: &[&Step],
+    handler: bool,
+) -> Result<()> {
+    let ids: HashSet<&str> = visible.iter().filter_map(|s| s.id.as_deref()).collect();
+    for (namespace, field) in variables {
+        match namespace {
+            Namespace::Error if !handler => {
+                bail!("error.{} is only available in on_failure steps.", field)
+            }
+            Namespace::Error if !ERROR_FIELDS.contains(field) => {
+                bail!("error.{} is unknown; use one of {:?}.", field, ERROR_FIELDS)
+            }
+            Namespace::Steps => {}
+            _ => continue,
+        }
+        let mut parts = field.splitn(2, '.');
+        let (id, output) = match (parts.next(), parts.next()) {
+            (Some(id), Some(output)) => (id, output),
+            _ => bail!("steps.{} should be written as steps.<id>.<output>.", field),
+        };
+        let step = visible
+            .iter()
+            .find(|step| step.id.as_deref() == Some(id))
+            .with_context(|| {
+                let mut known: Vec<_> = ids.iter().collect();
+                known.sort();
+                format!(
+                    "No step this one depends on has the id `{}`; known ids are {:?}.",
+                    id, known
+                )
+            })?;
+        match &step.outputs {
+            Some(outputs) if !outputs.contains(&output) => bail!(
+                "Step `{}` has no output `{}`; it provides {:?}.",
+                id,
+                output,
+                outputs
+            ),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// A workflow whose parameters have been parsed ahead of the run.
+pub struct Step {
+    id: Option<String>,
+    index: usize,
+    workflow_type: String,
+    workflow: &'static SupportedWorkflows,
+    /// The written indices of the steps whose outputs this one starts from; none for the
+    /// first step, which starts from the pipeline's input.
+    dependencies: Option<Vec<usize>>,
+    parameters: Vec<(&'static ParameterSpec, RawValue<Template>)>,
+    /// What the step emits, when known before it runs.
+    outputs: Option<Vec<&'static str>>,
+    condition: Option<Condition>,
+    on_false: OnFalse,
+    continue_on_error: bool,
+    retry: Option<Retry>,
+    timeout: Option<Duration>,
+    concurrency: Option<NonZeroUsize>,
+}
+
+impl Step {
+    /// The step's id, or its position and type when it has none.
+    fn name(&self) -> String {
+        match &self.id {
+            Some(id) => id.clone(),
+            None => format!("{} ({})", self.index + 1, self.workflow_type),
+        }
+    }
+
+    /// The rendered parameters as `name="value"`, masking secret ones and those rendered
+    /// from `secrets`.
+    fn describe(&self, payload: &Inputs) -> String {
+        let mut described = Vec::new();
+        for (spec, templates) in &self.parameters {
+            let secret = spec.secret
+                || templates
+                    .items()
+                    .iter()
+                    .any(|template| template.references(Namespace::Secrets));
+            match payload.get(spec.name) {
+                Some(_) if secret => described.push(format!("{}={}", spec.name, secret::MASK)),
+                Some(value) => described.push(format!("{}={:?}", spec.name, value.to_string())),
+                None => {}
+            }
+        }
+        described.join(" ")
+    }
+
+    fn fail(&self, kind: ErrorKind, source: anyhow::Error) -> StepError {
+        StepError {
+            step: self.name(),
+            kind,
+            source,
+        }
+    }
+
+    async fn execute(&self, context: &Context, input: &Outputs) -> Result<Vec<Outputs>, StepError> {
+        let mut payload = Inputs::new();
+        for (spec, templates) in &self.parameters {
+            let raw = templates
+                .try_map(|template| template.render(input, context))
+                .with_context(|| format!("Unable to render parameter `{}`.", spec.name))
+                .map_err(|e| self.fail(ErrorKind::Template, e))?;
+            if spec.secret {
+                raw.items()
+                    .into_iter()
+                    .for_each(|value| secret::register(value));
+            }
+            let value = spec
+                .parse(raw)
+                .with_context(|| format!("Invalid value for parameter `{}`.", spec.name))
+                .map_err(|e| self.fail(ErrorKind::Parse, e))?;
+            payload.insert(spec.name, value);
+        }
+        debug!(parameters = %self.describe(&payload), "Resolved parameters.");
+        let mut attempt = 1;
+        loop {
+            let result = self.attempt(context, payload.clone()).await;
+            let retry = match &self.retry {
+                Some(retry) if attempt < retry.attempts && retry.should_retry(&result) => retry,
+                _ => return result.map_err(|e| self.fail(ErrorKind::of(&e), e)),
+            };
+            let delay = retry.delay(attempt);
+            if context
+                .remaining()
+                .is_some_and(|remaining| remaining <= delay)
+            {
+                return result.map_err(|e| self.fail(ErrorKind::of(&e), e));
+            }
+            let reason = match &result {
+                Err(error) => format!("{:#}", error),
+                Ok(_) => "retryable status code".to_string(),
+            };
+            warn!(
+                "Step {} attempt {} of {} failed ({}); retrying in {:?}.",
+                self.name(),
+                attempt,
+                retry.attempts,
+                reason,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Runs the workflow once within the step's own timeout, dropping it at the deadline.
+    async fn attempt(&self, context: &Context, payload: Inputs) -> Result<Vec<Outputs>> {
+        let context = context.limit(self.timeout);
+        context.check_deadline()?;
+        let execution = self.workflow.execute(&context, payload);
+        let outputs = match context.deadline {
+            Some((deadline, limit)) => tokio::time::timeout_at(deadline.into(), execution)
+                .await
+                .map_err(|_| TimedOut { limit })??,
+            None => execution.await?,
+        };
+        if let (true, Some(declared)) = (context.strict, &self.outputs) {
+            check_outputs(declared, &outputs)?;
+        }
+        Ok(outputs)
+    }
+}
+
+/// Fails when a branch holds other outputs than the workflow declares.
+fn check_outputs(declared: &[&str], outputs: &[Outputs]) -> Result<()> {
+    for output in outputs {
+        let mut missing: Vec<_> = declared
+            .iter()
+            .filter(|key| !output.contains_key(*key))
+            .collect();
+        let mut undeclared: Vec<_> = output
+            .keys()
+            .filter(|key| !declared.contains(key))
+            .collect();
+        if missing.is_empty() && undeclared.is_empty() {
+            continue;
+        }
+        missing.sort();
+        undeclared.sort();
+        bail!(
+            "The outputs do not match the declared {:?}: missing {:?}, undeclared {:?}.",
+            declared,
+            missing,
+            undeclared
+        );
+    }
+    Ok(())
+}
+
+fn read_config(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("Unable to read {}.", path.display()))
+}
+
+/// Reads a configuration with what it includes.
+fn load_config(path: &Path) -> Result<Config> {
+    let source = read_config(path)?;
+    let expanded = include::expand(&source, path)?;
+    Ok(include::deserialize(&source, expanded.value)?)
+}
 
-    let mut context = Context::new(config);
-    if let Some(next) = context.next() {
-        next.execute(&mut context, HashMap::new())?;
+/// Runs one job, or every job in turn when `job` is `None`.
+async fn run(path: &str, job: Option<&str>, vars: &[(String, String)]) -> Result<()> {
+    let config = load_config(Path::new(path))?;
+    let names = match job {
+        Some(job) => vec![job],
+        None => config.job_names()?,
+    };
+    let mut pipelines = Vec::new();
+    for name in &names {
+        let mut pipeline = config
+            .compile(name)
+            .with_context(|| format!("Invalid job {}.", name))?;
+        for (name, value) in vars {
+            pipeline.set_var(name, value);
+        }
+        pipeline.path = Some(Path::new(path).canonicalize()?);
+        pipelines.push((name, pipeline));
+    }
+    if let [(_, pipeline)] = &pipelines[..] {
+        pipeline.run(&Context::new(pipeline)?).await?;
+        return Ok(());
     }
 
+    let mut failed = Vec::new();
+    for (name, pipeline) in &pipelines {
+        let result = match Context::new(pipeline) {
+            Ok(context) => pipeline.run(&context).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            error!("Job {} failed: {:#}", name, error);
+            failed.push(**name);
+        }
+    }
+    if !failed.is_empty() {
+        bail!(
+            "{} of {} jobs failed: {}.",
+            failed.len(),
+            pipelines.len(),
+            failed.join(", ")
+        );
+    }
     Ok(())
 }
+
+fn check(path: &str) -> Result<()> {
+    let problems = validate::validate(&read_config(Path::new(path))?, Path::new(path));
+    if problems.is_empty() {
+        println!("{} is valid.", path);
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{}: {}", path, problem);
+    }
+    bail!("Found {} problem(s) in {}.", problems.len(), path);
+}
+
+fn main() {
+    if let Err(error) = dispatch() {
+        // As returning the error would print it, with secrets masked.
+        eprintln!("Error: {}", secret::mask(&format!("{:?}", error)));
+        std::process::exit(1);
+    }
+}
+
+fn dispatch() -> Result<()> {
+    let (logging, args) = cli::Logging::extract(env::args().skip(1))?;
+    let subcommand = cli::Subcommand::parse(args)?;
+    // Long-running commands report what they start by default.
+    let quiet = match subcommand {
+        cli::Subcommand::Daemon { .. } | cli::Subcommand::Serve { .. } => Level::INFO,
+        _ => Level::WARN,
+    };
+    logging::init(logging.verbosity, logging.format, quiet);
+    match subcommand {
+        cli::Subcommand::Run { config, job, vars } => {
+            RUNTIME.block_on(run(&config, job.as_deref(), &vars))
+        }
+        cli::Subcommand::Validate { config } => check(&config),
+        cli::Subcommand::Daemon { configs, state } => {
+            let state = state.as_deref().unwrap_or(daemon::STATE_FILE);
+            RUNTIME.block_on(daemon::daemon(&configs, Path::new(state)))
+        }
+        cli::Subcommand::Serve { configs, listen } => {
+            let listen = listen.as_deref().unwrap_or(webhook::ADDRESS);
+            RUNTIME.block_on(webhook::serve(&configs, listen))
+        }
+        cli::Subcommand::Secret { store, name } => secret::store_from_stdin(&store, &name),
+        cli::Subcommand::List { json } => {
+            print!("{}", describe::list(json)?);
+            Ok(())
+        }
+        cli::Subcommand::Describe {
+            workflow_type,
+            json,
+        } => {
+            print!("{}", describe::describe(&workflow_type, json)?);
+            Ok(())
+        }
+        cli::Subcommand::Schema => {
+            println!("{}", serde_json::to_string_pretty(&schema::schema())?);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stand_in::{Request, TempDir};
+
+    fn respond(request: &Request) -> (u16, String) {
+        let body = match (&request.method[..], &request.path[..]) {
+            ("GET", path) if path.starts_with("/cgi-bin/gettoken") => {
+                r#"{"errcode": 0, "errmsg": "ok", "access_token": "t", "expires_in": 7200}"#
+            }
+            ("POST", "/cgi-bin/message/send?access_token=t")
+                if request.body.contains(r#""agentid":1"#) =>
+            {
+                r#"{"errcode": 0, "errmsg": "ok"}"#
+            }
+            ("GET", "/gists/g1") => r#"{"files": {"a.txt": {"content": "hello"}}}"#,
+            ("GET", "/status") => "ok",
+            _ => return (404, "{}".to_string()),
+        };
+        (200, body.to_string())
+    }
+
+    fn compile(workflow_type: &str, parameters: &[(&str, &str)]) -> Step {
+        let config = WorkflowConfig {
+            id: None,
+            workflow_type: workflow_type.to_string(),
+            needs: None,
+            parameters: parameters
+                .iter()
+                .map(|(k, v)| (k.to_string(), RawValue::Text(v.to_string())))
+                .collect(),
+            condition: None,
+            on_false: OnFalse::Continue,
+            continue_on_error: false,
+            retry: None,
+            timeout: None,
+            concurrency: None,
+        };
+        config.compile(0, Vec::new(), &[], false).unwrap()
+    }
+
+    #[test]
+    fn test_output_contracts() {
+        let url = stand_in::serve(respond);
+        let dir = TempDir::new("contracts");
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+
+        let archive = fs::File::create(path("archive.tar.gz")).unwrap();
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            archive,
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "inner.txt", &b"hello"[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        fs::write(
+            path("called.yml"),
+            "workflows:\n  - type: echo\n    parameters:\n      text: hi\n",
+        )
+        .unwrap();
+        let rss = r#"<rss version="2.0"><channel><title>t</title><link>l</link>
+            <description>d</description><item><title>a</title><link>b</link></item>
+            </channel></rss>"#;
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>t</title><id>i</id>
+            <updated>2021-01-01T00:00:00Z</updated><entry><title>a</title><id>e</id>
+            <updated>2021-01-01T00:00:00Z</updated><link href="b"/></entry></feed>"#;
+        let steps = vec![
+            compile("echo", &[("text", "hi")]),
+            compile("http", &[("url", &format!("{}/status", url))]),
+            compile("command", &[("program", "true")]),
+            compile(
+                "save",
+                &[("text", "hi"), ("destination", &path("saved.txt"))],
+            ),
+            compile("read", &[("path", &path("saved.txt"))]),
+            compile("call", &[("config", &path("called.yml"))]),
+            compile(
+                "decompress",
+                &[
+                    ("path", &path("archive.tar.gz")),
+                    ("destination", &path("unpacked")),
+                ],
+            ),
+            compile("rss", &[("text", rss)]),
+            compile("atom", &[("text", atom)]),
+            compile(
+                "gist",
+                &[
+                    ("action", "get"),
+                    ("gist_id", "g1"),
+                    ("file_name", "a.txt"),
+                    ("base_url", &url),
+                ],
+            ),
+            compile(
+                "wechat",
+                &[
+                    ("corp_id", "c"),
+                    ("secret", "c0rp-s3cret"),
+                    ("agent_id", "1"),
+                    ("text", "hi"),
+                    ("base_url", &url),
+                ],
+            ),
+        ];
+        let mut covered: Vec<_> = steps.iter().map(|s| &s.workflow_type[..]).collect();
+        covered.sort();
+        let mut registered: Vec<_> = WORKFLOWS.keys().copied().collect();
+        registered.sort();
+        assert_eq!(covered, registered);
+
+        let context = Context::new(&Pipeline::default()).unwrap();
+        assert!(context.strict);
+        for step in &steps {
+            let outputs = RUNTIME
+                .block_on(step.execute(&context, &Outputs::new()))
+                .unwrap_or_else(|e| panic!("{}: {:#}", e, e.source));
+            assert!(!outputs.is_empty(), "{} emitted no branch", step.name());
+            if let Some(declared) = &step.outputs {
+                check_outputs(declared, &outputs).unwrap();
+            }
+        }
+        assert_eq!(
+            fs::read_to_string(path("unpacked/inner.txt")).unwrap(),
+            "hello"
+        );
+
+        let step = compile(
+            "gist",
+            &[
+                ("action", "get"),
+                ("gist_id", "g1"),
+                ("file_name", "b.txt"),
+                ("base_url", &url),
+            ],
+        );
+        let error = RUNTIME
+            .block_on(step.execute(&context, &Outputs::new()))
+            .unwrap_err();
+        assert_eq!(error.message(), "The gist has no file named b.txt.");
+    }
+
+    #[test]
+    fn test_jobs() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+vars:
+  who: world
+timeout: 1m
+jobs:
+  xbox:
+    vars:
+      who: xbox
+    timeout: 10s
+    steps:
+      - type: echo
+        parameters:
+          text: "{vars.who}"
+  github:
+    steps: []
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.job_names().unwrap(), vec!["github", "xbox"]);
+        let pipeline = config.compile("xbox").unwrap();
+        let context = Context::new(&pipeline).unwrap();
+        assert_eq!(context.vars["who"], "xbox");
+        assert_eq!(pipeline.timeout, Some(Duration::from_secs(10)));
+        assert_eq!(
+            config.compile("github").unwrap().timeout,
+            Some(Duration::from_secs(60))
+        );
+        assert!(config.compile(DEFAULT_JOB).is_err());
+
+        let config: Config =
+            serde_yaml::from_str("workflows:\n  - type: echo\n    parameters:\n      text: hi\n")
+                .unwrap();
+        assert_eq!(config.job_names().unwrap(), vec![DEFAULT_JOB]);
+        assert_eq!(config.compile(DEFAULT_JOB).unwrap().steps.len(), 1);
+    }
+
+    #[test]
+    fn test_needs() {
+        let url = stand_in::serve(respond);
+        let dir = TempDir::new("needs");
+        fs::write(dir.join("note.txt"), "hi").unwrap();
+        let source = format!(
+            r#"
+workflows:
+  - type: save
+    needs: [page, note]
+    parameters:
+      text: "{{input.status_code}} {{input.text}}"
+      destination: {dir}/joined.txt
+  - id: page
+    type: http
+    needs: []
+    parameters:
+      url: {url}/status
+  - id: note
+    type: read
+    needs: []
+    parameters:
+      path: {dir}/note.txt
+"#,
+            dir = dir.display(),
+            url = url
+        );
+        let config: Config = serde_yaml::from_str(&source).unwrap();
+        let pipeline = config.compile(DEFAULT_JOB).unwrap();
+        let order: Vec<_> = pipeline.steps.iter().map(|step| step.index).collect();
+        assert_eq!(order, vec![1, 2, 0]);
+        let context = Context::new(&pipeline).unwrap();
+        RUNTIME.block_on(pipeline.run(&context)).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("joined.txt")).unwrap(),
+            "200 hi"
+        );
+    }
+
+    #[test]
+    fn test_needs_with_predecessors() {
+        let dir = TempDir::new("predecessors");
+        fs::write(dir.join("b.txt"), "from b").unwrap();
+        fs::write(dir.join("c.txt"), "from c").unwrap();
+        let source = format!(
+            r#"
+workflows:
+  - type: save
+    needs: [c]
+    parameters:
+      text: "{{input.text}}"
+      destination: {dir}/a.txt
+  - type: read
+    parameters:
+      path: {dir}/b.txt
+  - id: c
+    type: read
+    needs: []
+    parameters:
+      path: {dir}/c.txt
+  - type: save
+    parameters:
+      text: "{{input.text}}"
+      destination: {dir}/d.txt
+  - id: item
+    type: rss
+    needs: []
+    parameters:
+      text: "<rss version='2.0'><channel><title>t</title><link>l</link><description>d</description><item><title>x</title></item><item><title>y</title></item></channel></rss>"
+  - id: note
+    type: save
+    needs: []
+    parameters:
+      text: note
+      destination: "{dir}/note-{{uuid()}}.txt"
+  - type: save
+    needs: [item, note]
+    parameters:
+      text: "{{steps.item.title}}"
+      destination: {dir}/{{input.title}}.txt
+"#,
+            dir = dir.display()
+        );
+        assert!(validate::validate(&source, Path::new("test.yml")).is_empty());
+        let config: Config = serde_yaml::from_str(&source).unwrap();
+        let pipeline = config.compile(DEFAULT_JOB).unwrap();
+        let context = Context::new(&pipeline).unwrap();
+        RUNTIME.block_on(pipeline.run(&context)).unwrap();
+        let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("a.txt"), "from c");
+        assert_eq!(read("d.txt"), "from c");
+        assert_eq!(read("x.txt"), "x");
+        assert_eq!(read("y.txt"), "y");
+        let notes = fs::read_dir(&*dir)
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with("note-")
+            })
+            .count();
+        assert_eq!(notes, 1);
+    }
+
+    #[test]
+    fn test_needs_trigger_input() {
+        let dir = TempDir::new("trigger_input");
+        let source = format!(
+            r#"
+jobs:
+  hook:
+    triggers:
+      webhook:
+        path: /hook
+    steps:
+      - type: save
+        parameters:
+          text: "{{input.method}} {{input.body}}"
+          destination: {dir}/first.txt
+      - type: save
+        needs: []
+        parameters:
+          text: "{{input.method}} {{input.body}}"
+          destination: {dir}/root.txt
+"#,
+            dir = dir.display()
+        );
+        assert!(validate::validate(&source, Path::new("test.yml")).is_empty());
+        let config: Config = serde_yaml::from_str(&source).unwrap();
+        let pipeline = config.compile("hook").unwrap();
+        let context = Context::new(&pipeline).unwrap();
+        let mut input = Outputs::new();
+        input.insert("method", "POST".to_string());
+        input.insert("body", "push".to_string());
+        RUNTIME
+            .block_on(pipeline.run_with_input(&context, &input))
+            .unwrap();
+        for name in &["first.txt", "root.txt"] {
+            assert_eq!(fs::read_to_string(dir.join(name)).unwrap(), "POST push");
+        }
+    }
+
+    #[test]
+    fn test_references() {
+        let compile = |steps: &str| {
+            let source = format!("workflows:\n{}", steps);
+            let config: Config = serde_yaml::from_str(&source).unwrap();
+            config
+                .compile(DEFAULT_JOB)
+                .map_err(|error| format!("{:#}", error))
+        };
+        let page = "  - id: page\n    type: http\n    parameters:\n      url: http://localhost\n";
+        let echo = |text: &str, needs: &str| {
+            format!(
+                "  - type: echo\n    {}\n    parameters:\n      text: \"{}\"\n",
+                needs, text
+            )
+        };
+
+        assert!(compile(&(page.to_string() + &echo("{steps.page.text}", ""))).is_ok());
+        let error = compile(&(page.to_string() + &echo("{steps.pages.text}", "")))
+            .err()
+            .unwrap();
+        assert!(
+            error.contains(
+                r#"No step this one depends on has the id `pages`; known ids are ["page"]."#
+            ),
+            "{}",
+            error
+        );
+        let error = compile(&(page.to_string() + &echo("{steps.page.body}", "")))
+            .err()
+            .unwrap();
+        assert!(
+            error.contains(
+                r#"Step `page` has no output `body`; it provides ["status_code", "text"]."#
+            ),
+            "{}",
+            error
+        );
+        // A step that does not follow `page` cannot read it.
+        let error = compile(&(page.to_string() + &echo("{steps.page.text}", "needs: []")))
+            .err()
+            .unwrap();
+        assert!(error.contains("known ids are []"), "{}", error);
+
+        // Each branch of a fan-out reads its own item.
+        let dir = TempDir::new("references");
+        fs::write(dir.join("a.txt"), "first").unwrap();
+        fs::write(dir.join("b.txt"), "second").unwrap();
+        let steps = format!(
+            "  - id: item\n    type: rss\n    parameters:\n      text: \"<rss version='2.0'><channel>\
+             <title>t</title><link>l</link><description>d</description>\
+             <item><title>a</title></item><item><title>b</title></item></channel></rss>\"\n\
+             - type: read\n    parameters:\n      path: \"{}/{{steps.item.title}}.txt\"\n",
+            dir.display()
+        );
+        let steps = steps.replace("\n- type", "\n  - type");
+        let pipeline = compile(&steps).unwrap();
+        let context = Context::new(&pipeline).unwrap();
+        let ends = RUNTIME.block_on(pipeline.run(&context)).unwrap();
+        let texts: Vec<_> = ends.iter().map(|end| &end["text"][..]).collect();
+        assert_eq!(texts, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_failure_handling() {
+        let dir = TempDir::new("failure");
+        let run = |source: String| {
+            let config: Config = serde_yaml::from_str(&source).unwrap();
+            let pipeline = config.compile(DEFAULT_JOB).unwrap();
+            let context = Context::new(&pipeline).unwrap();
+            RUNTIME.block_on(pipeline.run(&context))
+        };
+
+        let ends = run(format!(
+            r#"
+workflows:
+  - type: read
+    continue_on_error: true
+    parameters:
+      path: {dir}/missing.txt
+  - type: save
+    parameters:
+      text: continued
+      destination: {dir}/continued.txt
+  - type: read
+    continue_on_error: true
+    parameters:
+      path: {dir}/missing.txt
+"#,
+            dir = dir.display()
+        ))
+        .unwrap();
+        assert_eq!(ends, vec![Outputs::new()]);
+        assert!(dir.join("continued.txt").exists());
+
+        let error = run(format!(
+            r#"
+workflows:
+  - id: broken
+    type: read
+    parameters:
+      path: {dir}/missing.txt
+on_failure:
+  - type: save
+    parameters:
+      text: "{{error.step}}|{{error.type}}|{{error.message}}"
+      destination: {dir}/error.txt
+"#,
+            dir = dir.display()
+        ))
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Step broken failed (io error).");
+        let reported = fs::read_to_string(dir.join("error.txt")).unwrap();
+        assert_eq!(reported, "broken|io|No such file or directory (os error 2)");
+    }
+
+    #[test]
+    fn test_secrets() {
+        let dir = TempDir::new("secrets");
+        fs::write(dir.join("token.txt"), "tok3n\n").unwrap();
+        let source = format!(
+            r#"
+secrets:
+  token:
+    file: token.txt
+workflows:
+  - type: save
+    parameters:
+      text: "{{secrets.token}}"
+      destination: {dir}/saved.txt
+  - type: call
+    parameters:
+      config: "{{secrets.token}}.yml"
+on_failure:
+  - type: save
+    parameters:
+      text: "{{error.message}}"
+      destination: {dir}/error.txt
+"#,
+            dir = dir.display()
+        );
+        let config: Config = serde_yaml::from_str(&source).unwrap();
+        let mut pipeline = config.compile(DEFAULT_JOB).unwrap();
+        pipeline.path = Some(dir.join("secrets.yml"));
+        let context = Context::new(&pipeline).unwrap();
+        let error = RUNTIME.block_on(pipeline.run(&context)).unwrap_err();
+        let saved = fs::read_to_string(dir.join("saved.txt")).unwrap();
+        let reported = fs::read_to_string(dir.join("error.txt")).unwrap();
+        assert_eq!(saved, "tok3n");
+        assert!(format!("{:#}", error).contains("tok3n"));
+        assert!(!secret::mask(&format!("{:#}", error)).contains("tok3n"));
+        assert!(reported.contains("***.yml"), "{}", reported);
+
+        let step = &pipeline.steps[0];
+        let mut payload = Inputs::new();
+        payload.insert("text", Value::String("tok3n".to_string()));
+        payload.insert("destination", Value::String("saved.txt".to_string()));
+        assert_eq!(
+            step.describe(&payload),
+            r#"text=*** destination="saved.txt""#
+        );
+
+        let template = Template::parse("{secrets.other}").unwrap();
+        let error = template.render(&Outputs::new(), &context).unwrap_err();
+        assert!(error.to_string().contains("declare it under secrets"));
+
+        let step = compile(
+            "wechat",
+            &[
+                ("corp_id", "c"),
+                ("secret", "w3chat-s3cret"),
+                ("agent_id", "1"),
+                ("text", "hi"),
+                ("base_url", "http://127.0.0.1:1"),
+            ],
+        );
+        let error = RUNTIME
+            .block_on(step.execute(&context, &Outputs::new()))
+            .unwrap_err();
+        let message = format!("{:#}", error.source);
+        assert!(message.contains("w3chat-s3cret"), "{}", message);
+        assert!(!secret::mask(&message).contains("w3chat-s3cret"));
+    }
+
+    /// Collects what a subscriber writes.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_logging() {
+        let url = stand_in::serve(respond);
+        let source = format!(
+            r#"
+jobs:
+  notify:
+    steps:
+      - id: send
+        type: wechat
+        parameters:
+          corp_id: c
+          secret: s3cret
+          agent_id: 1
+          text: hi
+          base_url: {}
+"#,
+            url
+        );
+        let config: Config = serde_yaml::from_str(&source).unwrap();
+        let pipeline = config.compile("notify").unwrap();
+        let context = Context::new(&pipeline).unwrap();
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_span_list(true)
+            .with_max_level(Level::DEBUG)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            RUNTIME.block_on(pipeline.run(&context)).unwrap();
+        });
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(!log.contains("s3cret"), "{}", log);
+        let resolved = events
+            .iter()
+            .find(|event| event["fields"]["message"] == "Resolved parameters.")
+            .unwrap();
+        assert!(resolved["fields"]["parameters"]
+            .as_str()
+            .unwrap()
+            .contains("secret=***"));
+        let finished = events
+            .iter()
+            .find(|event| event["fields"]["message"] == "Step finished.")
+            .unwrap();
+        assert_eq!(finished["fields"]["bytes"], 1);
+        assert!(finished["fields"]["elapsed_ms"].is_u64());
+        assert_eq!(finished["spans"][0]["job"], "notify");
+        assert_eq!(finished["spans"][1]["step"], "send");
+    }
+
+    #[test]
+    fn test_concurrency() {
+        let dir = TempDir::new("fan_out");
+        // Each run marks itself as running, then notes how many runs are marked.
+        let script = dir.join("overlap.sh");
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\ntouch {0}/running.$$\nsleep 0.2\n\
+                 ls {0} | grep -c '^running' > {0}/peak.$$\nrm {0}/running.$$\n",
+                dir.display()
+            ),
+        )
+        .unwrap();
+        let executable = std::os::unix::fs::PermissionsExt::from_mode(0o755);
+        fs::set_permissions(&script, executable).unwrap();
+        let items: String = ["a", "b", "c", "d"]
+            .iter()
+            .map(|title| format!("<item><title>{}</title><link>l</link></item>", title))
+            .collect();
+        let feed = format!(
+            "<rss version='2.0'><channel><title>t</title><link>l</link><description>d</description>{}</channel></rss>",
+            items
+        );
+        let run = |limit: &str| {
+            let source = format!(
+                r#"
+{limit}
+workflows:
+  - id: item
+    type: rss
+    concurrency: 4
+    parameters:
+      text: "{feed}"
+  - type: save
+    parameters:
+      text: "{{input.title}}"
+      destination: {dir}/{{input.title}}.txt
+  - type: command
+    parameters:
+      program: {script}
+  - type: read
+    parameters:
+      path: {dir}/{{steps.item.title}}.txt
+"#,
+                limit = limit,
+                feed = feed,
+                dir = dir.display(),
+                script = script.display()
+            );
+            let config: Config = serde_yaml::from_str(&source).unwrap();
+            let pipeline = config.compile(DEFAULT_JOB).unwrap();
+            let context = Context::new(&pipeline).unwrap();
+            let ends = RUNTIME.block_on(pipeline.run(&context)).unwrap();
+            let texts: Vec<_> = ends.iter().map(|end| end["text"].clone()).collect();
+            assert_eq!(texts, vec!["a", "b", "c", "d"]);
+            let mut peak = 0;
+            for entry in fs::read_dir(&*dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("peak")
+                {
+                    peak = peak.max(fs::read_to_string(&path).unwrap().trim().parse().unwrap());
+                    fs::remove_file(path).unwrap();
+                }
+            }
+            peak
+        };
+        assert!(run("") > 1);
+        // The pipeline's limit holds for every step, whatever a fan-out allows.
+        let peak = run("concurrency: 2");
+        assert!(peak > 0 && peak <= 2, "{}", peak);
+    }
+
+    #[test]
+    fn test_check_outputs() {
+        let mut output = Outputs::new();
+        output.insert("status_code", "200".to_string());
+        assert!(check_outputs(&["status_code"], &[output.clone()]).is_ok());
+        let error = check_outputs(&["status_code", "text"], &[output]).unwrap_err();
+        assert!(error.to_string().contains(r#"missing ["text"]"#));
+    }
+}
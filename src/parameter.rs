@@ -0,0 +1,257 @@
+use crate::parser::Template;
+use anyhow::{anyhow, bail, Result};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use serde_yaml::Value as Yaml;
+use std::fmt;
+use strum::Display;
+
+/// The kind of value a parameter takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ParameterType {
+    String,
+    Int,
+    /// `true` or `false`.
+    Bool,
+    /// A YAML sequence whose items are templates.
+    List,
+    /// A YAML mapping whose values are templates.
+    Map,
+}
+
+/// Declares one parameter of a workflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ParameterSpec {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub kind: ParameterType,
+    pub required: bool,
+    /// Used when the parameter is left out.
+    pub default: Option<&'static str>,
+    /// Masks the rendered value wherever the program prints, as with `secrets`.
+    pub secret: bool,
+    pub description: &'static str,
+}
+
+/// Declares one output of a workflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct OutputSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+impl OutputSpec {
+    pub const fn new(name: &'static str, description: &'static str) -> Self {
+        Self { name, description }
+    }
+}
+
+impl ParameterSpec {
+    pub const fn new(name: &'static str, kind: ParameterType, description: &'static str) -> Self {
+        Self {
+            name,
+            kind,
+            required: false,
+            default: None,
+            secret: false,
+            description,
+        }
+    }
+
+    pub const fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub const fn default(mut self, value: &'static str) -> Self {
+        self.default = Some(value);
+        self
+    }
+
+    pub const fn secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+
+    /// Converts a rendered value to the declared type.
+    pub fn parse(&self, raw: RawValue<String>) -> Result<Value> {
+        let show = |value: &str| {
+            if self.secret {
+                "The value".to_string()
+            } else {
+                format!("`{}`", value)
+            }
+        };
+        Ok(match (self.kind, raw) {
+            (ParameterType::String, RawValue::Text(text)) => Value::String(text),
+            (ParameterType::Int, RawValue::Text(text)) => Value::Int(
+                text.trim()
+                    .parse()
+                    .map_err(|_| anyhow!("{} is not an integer.", show(&text)))?,
+            ),
+            (ParameterType::Bool, RawValue::Text(text)) => match &text.trim().to_lowercase()[..] {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => bail!("{} is neither true nor false.", show(&text)),
+            },
+            (ParameterType::List, RawValue::List(items)) => Value::List(items),
+            (ParameterType::Map, RawValue::Map(entries)) => Value::Map(entries),
+            (kind, raw) => bail!("Expected a {}, found a {}.", kind, raw.shape()),
+        })
+    }
+}
+
+/// A parameter as written in the configuration: a scalar, a list or a map of `T`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawValue<T> {
+    Text(T),
+    List(Vec<T>),
+    Map(Vec<(String, T)>),
+}
+
+impl<T> RawValue<T> {
+    pub fn shape(&self) -> &'static str {
+        match self {
+            RawValue::Text(_) => "scalar",
+            RawValue::List(_) => "list",
+            RawValue::Map(_) => "map",
+        }
+    }
+
+    /// Every `T` in the value, in order.
+    pub fn items(&self) -> Vec<&T> {
+        match self {
+            RawValue::Text(text) => vec![text],
+            RawValue::List(items) => items.iter().collect(),
+            RawValue::Map(entries) => entries.iter().map(|(_, value)| value).collect(),
+        }
+    }
+
+    pub fn try_map<U, E>(&self, mut f: impl FnMut(&T) -> Result<U, E>) -> Result<RawValue<U>, E> {
+        Ok(match self {
+            RawValue::Text(text) => RawValue::Text(f(text)?),
+            RawValue::List(items) => {
+                RawValue::List(items.iter().map(&mut f).collect::<Result<_, _>>()?)
+            }
+            RawValue::Map(entries) => RawValue::Map(
+                entries
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), f(value)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+}
+
+impl RawValue<Template> {
+    /// The value itself when none of its templates has placeholders.
+    pub fn as_literal(&self) -> Option<RawValue<String>> {
+        self.try_map(|template| template.as_literal().ok_or(()))
+            .ok()
+    }
+}
+
+impl<'de> Deserialize<'de> for RawValue<String> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let scalar = |value: Yaml| match value {
+            Yaml::String(text) => Ok(text),
+            Yaml::Number(number) => Ok(number.to_string()),
+            Yaml::Bool(flag) => Ok(flag.to_string()),
+            Yaml::Null => Ok(String::new()),
+            _ => Err(D::Error::custom("lists and maps can only hold scalars")),
+        };
+        Ok(match Yaml::deserialize(deserializer)? {
+            Yaml::Sequence(items) => {
+                RawValue::List(items.into_iter().map(scalar).collect::<Result<_, _>>()?)
+            }
+            Yaml::Mapping(entries) => RawValue::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| Ok((scalar(key)?, scalar(value)?)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            value => RawValue::Text(scalar(value)?),
+        })
+    }
+}
+
+/// A parameter value after rendering and parsing, as handed to `Workflow::execute`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Int(i64),
+    Bool(bool),
+    List(Vec<String>),
+    Map(Vec<(String, String)>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(text) => write!(f, "{}", text),
+            Value::Int(number) => write!(f, "{}", number),
+            Value::Bool(flag) => write!(f, "{}", flag),
+            Value::List(items) => write!(f, "[{}]", items.join(", ")),
+            Value::Map(entries) => {
+                let entries: Vec<_> = entries
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> RawValue<String> {
+        RawValue::Text(value.to_string())
+    }
+
+    #[test]
+    fn test_parse() {
+        let spec = ParameterSpec::new("agent_id", ParameterType::Int, "");
+        assert_eq!(spec.parse(text(" 42")).unwrap(), Value::Int(42));
+        let error = spec.parse(text("abc")).unwrap_err();
+        assert_eq!(error.to_string(), "`abc` is not an integer.");
+        let error = spec.secret().parse(text("abc")).unwrap_err();
+        assert_eq!(error.to_string(), "The value is not an integer.");
+
+        let spec = ParameterSpec::new("daemon", ParameterType::Bool, "");
+        assert_eq!(spec.parse(text("True")).unwrap(), Value::Bool(true));
+        assert!(spec.parse(text("yes")).is_err());
+
+        let spec = ParameterSpec::new("args", ParameterType::List, "");
+        let error = spec.parse(text("-v")).unwrap_err();
+        assert_eq!(error.to_string(), "Expected a list, found a scalar.");
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let raw: RawValue<String> = serde_yaml::from_str("[a, 1, true]").unwrap();
+        assert_eq!(
+            raw,
+            RawValue::List(vec!["a".to_string(), "1".to_string(), "true".to_string()])
+        );
+        let raw: RawValue<String> = serde_yaml::from_str("{b: 2, a: x}").unwrap();
+        assert_eq!(
+            raw,
+            RawValue::Map(vec![
+                ("b".to_string(), "2".to_string()),
+                ("a".to_string(), "x".to_string())
+            ])
+        );
+        assert_eq!(
+            serde_yaml::from_str::<RawValue<String>>("3").unwrap(),
+            text("3")
+        );
+        assert!(serde_yaml::from_str::<RawValue<String>>("[[a]]").is_err());
+    }
+}
@@ -1,92 +1,391 @@
-use crate::Context;
-use anyhow::{anyhow, Context as _, Result};
+use crate::function::{Function, FUNCTIONS};
+use crate::{Context, Outputs};
+use anyhow::{Context as _, Result};
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, tag},
-    character::complete::{char, multispace0},
-    combinator::map,
-    multi::fold_many1,
-    sequence::{delimited, preceded, separated_pair},
-    IResult,
+    bytes::complete::{is_not, take_while, take_while1},
+    character::complete::{char, digit1, multispace0},
+    combinator::{all_consuming, cut, map, map_opt, opt, peek, recognize},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::{many0, separated_list1},
+    sequence::{delimited, pair, preceded, separated_pair, tuple},
+    Err,
 };
-use std::collections::HashMap;
-
-pub fn fulfill(raw: &str, input: &HashMap<String, String>, context: &Context) -> Result<String> {
-    let (_, texts) = parse(raw).map_err(|_| anyhow!("Unable to parse expression {}.", raw))?;
-    let mut result = String::new();
-    for text in texts {
-        result.push_str(match text {
-            Text::Literal(s) => s,
-            Text::Expression(Expression { namespace, field }) if namespace == "env" => context
+use std::fmt;
+
+const NAMESPACES: [&str; 6] = ["input", "env", "vars", "steps", "error", "secrets"];
+
+pub(crate) type IResult<'a, O> = nom::IResult<&'a str, O, VerboseError<&'a str>>;
+
+/// Describes where a template stopped making sense and what the parser wanted there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub column: usize,
+    pub expected: String,
+    pub found: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "column {}: expected {}, found {}",
+            self.column, self.expected, self.found
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    fn new(raw: &str, rest: &str, expected: &str) -> Self {
+        let offset = raw.len() - rest.len();
+        let expected = match expected {
+            "namespace" => format!("one of the namespaces {}", NAMESPACES.join(", ")),
+            "function" => format!("one of the functions {}", FUNCTIONS.join(", ")),
+            _ => expected.to_string(),
+        };
+        let token: String = rest
+            .chars()
+            .take_while(|c| !c.is_whitespace() && !"{}.(),".contains(*c))
+            .collect();
+        let found = match rest.chars().next() {
+            None => "end of input".to_string(),
+            Some(c) if c.is_whitespace() => "whitespace".to_string(),
+            Some(c) if token.is_empty() => format!("`{}`", c),
+            Some(_) => format!("`{}`", token),
+        };
+
+        Self {
+            column: raw[..offset].chars().count() + 1,
+            expected,
+            found,
+        }
+    }
+}
+
+/// A parameter value compiled once when the configuration is loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    texts: Vec<Text>,
+}
+
+impl Template {
+    pub fn parse(raw: &str) -> Result<Self, ParseError> {
+        parse(raw).map(|texts| Self { texts })
+    }
+
+    /// A template that renders to `value` verbatim.
+    pub fn literal(value: &str) -> Self {
+        Self {
+            texts: vec![Text::Literal(value.to_string())],
+        }
+    }
+
+    /// The rendered text when the template has no placeholders.
+    pub fn as_literal(&self) -> Option<String> {
+        let mut result = String::new();
+        for text in &self.texts {
+            match text {
+                Text::Literal(s) => result.push_str(s),
+                Text::Expression(_) => return None,
+            }
+        }
+        Some(result)
+    }
+
+    /// Lists every `namespace.field` the template reads, in order of appearance.
+    pub fn variables(&self) -> Vec<(Namespace, &str)> {
+        let mut variables = Vec::new();
+        for text in &self.texts {
+            if let Text::Expression(expression) = text {
+                expression.collect_variables(&mut variables);
+            }
+        }
+        variables
+    }
+
+    pub fn references(&self, namespace: Namespace) -> bool {
+        self.variables().iter().any(|(ns, _)| *ns == namespace)
+    }
+
+    pub fn render(&self, input: &Outputs, context: &Context) -> Result<String> {
+        let mut result = String::new();
+        for text in &self.texts {
+            match text {
+                Text::Literal(s) => result.push_str(s),
+                Text::Expression(expression) => {
+                    result.push_str(&expression.evaluate(input, context)?)
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Text {
+    Literal(String),
+    Expression(Expression),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    Input,
+    Env,
+    Vars,
+    Steps,
+    Error,
+    /// Declared under `secrets`; their values are masked wherever the program prints.
+    Secrets,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Variable {
+    namespace: Namespace,
+    field: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Expression {
+    Literal(String),
+    Variable(Variable),
+    Call(Function, Vec<Expression>),
+}
+
+impl Expression {
+    pub(crate) fn collect_variables<'a>(&'a self, variables: &mut Vec<(Namespace, &'a str)>) {
+        match self {
+            Expression::Literal(_) => {}
+            Expression::Variable(variable) => variables.push((variable.namespace, &variable.field)),
+            Expression::Call(_, arguments) => {
+                for argument in arguments {
+                    argument.collect_variables(variables);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn evaluate(&self, input: &Outputs, context: &Context) -> Result<String> {
+        Ok(match self {
+            Expression::Literal(s) => s.clone(),
+            Expression::Variable(Variable {
+                namespace: Namespace::Env,
+                field,
+            }) => context
                 .env
                 .get(field)
-                .with_context(|| format!("Missing {}.", field))?,
-            Text::Expression(Expression {
-                namespace: _,
+                .with_context(|| format!("Missing {}.", field))?
+                .clone(),
+            Expression::Variable(Variable {
+                namespace: Namespace::Input,
                 field,
             }) => input
+                .get(&field[..])
+                .with_context(|| format!("Missing {}.", field))?
+                .clone(),
+            Expression::Variable(Variable {
+                namespace: Namespace::Vars,
+                field,
+            }) => context
+                .vars
+                .get(field)
+                .with_context(|| format!("Missing {}.", field))?
+                .clone(),
+            Expression::Variable(Variable {
+                namespace: Namespace::Steps,
+                field,
+            }) => context
+                .output(field)
+                .with_context(|| format!("Missing {}.", field))?
+                .clone(),
+            Expression::Variable(Variable {
+                namespace: Namespace::Error,
+                field,
+            }) => context
+                .error
+                .get(&field[..])
+                .with_context(|| format!("Missing {}.", field))?
+                .clone(),
+            Expression::Variable(Variable {
+                namespace: Namespace::Secrets,
+                field,
+            }) => context
+                .secrets
                 .get(field)
-                .with_context(|| format!("Missing {}.", field))?,
-        });
+                .with_context(|| format!("Missing secrets.{}; declare it under secrets.", field))?
+                .clone(),
+            Expression::Call(function, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(input, context))
+                    .collect::<Result<Vec<_>>>()?;
+                function.call(&arguments, context.timezone)?
+            }
+        })
     }
+}
 
-    Ok(result)
+fn literal(input: &str) -> IResult<'_, Text> {
+    map(is_not("{"), |s: &str| Text::Literal(s.to_string()))(input)
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum Text<'a> {
-    Literal(&'a str),
-    Expression(Expression<'a>),
+fn identifier(input: &str) -> IResult<'_, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || c == '_')(input)
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct Expression<'a> {
-    namespace: &'a str,
-    field: &'a str,
+fn namespace(input: &str) -> IResult<'_, Namespace> {
+    context(
+        "namespace",
+        map_opt(identifier, |ns: &str| match ns {
+            "input" => Some(Namespace::Input),
+            "env" => Some(Namespace::Env),
+            "vars" => Some(Namespace::Vars),
+            "steps" => Some(Namespace::Steps),
+            "error" => Some(Namespace::Error),
+            "secrets" => Some(Namespace::Secrets),
+            _ => None,
+        }),
+    )(input)
+}
+
+/// A field name ends at whitespace, punctuation of templates and calls, and the characters
+/// of condition operators, so `input.code!=200` reads `code`.
+fn field(input: &str) -> IResult<'_, &str> {
+    context(
+        "a field name",
+        take_while1(|c: char| !c.is_whitespace() && !"{}(),=!<>&|".contains(c)),
+    )(input)
 }
 
-fn literal(input: &str) -> IResult<&str, Text> {
-    map(is_not("{"), |s: &str| Text::Literal(s))(input)
+fn variable(input: &str) -> IResult<'_, Variable> {
+    map(
+        separated_pair(namespace, cut(context("`.`", char('.'))), cut(field)),
+        |(namespace, field)| Variable {
+            namespace,
+            field: field.to_string(),
+        },
+    )(input)
 }
 
-fn namespace(input: &str) -> IResult<&str, &str> {
-    alt((tag("input"), tag("env")))(input)
+fn string(input: &str) -> IResult<'_, &str> {
+    alt((
+        delimited(
+            char('"'),
+            take_while(|c| c != '"'),
+            cut(context("a closing quote `\"`", char('"'))),
+        ),
+        delimited(
+            char('\''),
+            take_while(|c| c != '\''),
+            cut(context("a closing quote `'`", char('\''))),
+        ),
+    ))(input)
 }
 
-fn field(input: &str) -> IResult<&str, &str> {
-    is_not("\t }")(input)
+fn number(input: &str) -> IResult<'_, &str> {
+    recognize(tuple((
+        opt(char('-')),
+        digit1,
+        opt(pair(char('.'), digit1)),
+    )))(input)
 }
 
-fn expression(input: &str) -> IResult<&str, Expression> {
-    map(separated_pair(namespace, tag("."), field), |(ns, f)| {
-        Expression {
-            namespace: ns,
-            field: f,
-        }
-    })(input)
+pub(crate) fn argument(input: &str) -> IResult<'_, Expression> {
+    alt((
+        map(alt((string, number)), |s| {
+            Expression::Literal(s.to_string())
+        }),
+        expression,
+    ))(input)
+}
+
+pub(crate) fn failure<'a, O>(input: &'a str, expected: &'static str) -> IResult<'a, O> {
+    Err(Err::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(expected))],
+    }))
+}
+
+fn call(input: &str) -> IResult<'_, Expression> {
+    let (rest, name) = identifier(input)?;
+    let (rest, _) = preceded(multispace0, char('('))(rest)?;
+    let function: Function = match name.parse() {
+        Ok(function) => function,
+        Err(_) => return failure(input, "function"),
+    };
+    let (rest, arguments) = preceded(
+        multispace0,
+        alt((
+            map(peek(char(')')), |_| Vec::new()),
+            cut(separated_list1(
+                delimited(multispace0, char(','), multispace0),
+                cut(argument),
+            )),
+        )),
+    )(rest)?;
+    let (rest, _) = preceded(
+        multispace0,
+        cut(context("`,` or a closing parenthesis `)`", char(')'))),
+    )(rest)?;
+    if !function.accepts(arguments.len()) {
+        return failure(input, function.usage());
+    }
+    Ok((rest, Expression::Call(function, arguments)))
 }
 
-fn enclosed(input: &str) -> IResult<&str, Text> {
+pub(crate) fn expression(input: &str) -> IResult<'_, Expression> {
+    alt((call, map(variable, Expression::Variable)))(input)
+}
+
+fn enclosed(input: &str) -> IResult<'_, Text> {
     map(
         delimited(
             char('{'),
-            preceded(multispace0, expression),
-            preceded(multispace0, char('}')),
+            preceded(multispace0, cut(expression)),
+            preceded(multispace0, cut(context("a closing brace `}`", char('}')))),
         ),
-        |expr| Text::Expression(expr),
+        Text::Expression,
     )(input)
 }
 
-fn parse(input: &str) -> IResult<&str, Vec<Text>> {
-    fold_many1(alt((literal, enclosed)), Vec::new(), |mut acc, t| {
-        acc.push(t);
-        acc
-    })(input)
+fn parse(raw: &str) -> Result<Vec<Text>, ParseError> {
+    complete(
+        raw,
+        many0(alt((literal, enclosed))),
+        "a literal or an expression",
+    )
+}
+
+/// Runs `parser` over the whole of `raw`, translating nom's errors into a [`ParseError`].
+/// `fallback` describes what was expected when no parser left a more specific context.
+pub(crate) fn complete<'a, O>(
+    raw: &'a str,
+    parser: impl FnMut(&'a str) -> IResult<'a, O>,
+    fallback: &'static str,
+) -> Result<O, ParseError> {
+    match all_consuming(parser)(raw) {
+        Ok((_, output)) => Ok(output),
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+            let (rest, expected) = e
+                .errors
+                .iter()
+                .find_map(|(rest, kind)| match kind {
+                    VerboseErrorKind::Context(expected) => Some((*rest, *expected)),
+                    _ => None,
+                })
+                .unwrap_or((e.errors[0].0, fallback));
+            Err(ParseError::new(raw, rest, expected))
+        }
+        Err(Err::Incomplete(_)) => Err(ParseError::new(raw, "", "more input")),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Pipeline;
+    use std::sync::Arc;
 
     #[test]
     fn test_enclosed_expression() {
@@ -94,20 +393,20 @@ mod tests {
             enclosed("{ env.gist_secret }"),
             Ok((
                 "",
-                Text::Expression(Expression {
-                    namespace: "env",
-                    field: "gist_secret"
-                })
+                Text::Expression(Expression::Variable(Variable {
+                    namespace: Namespace::Env,
+                    field: "gist_secret".to_string()
+                }))
             ))
         );
         assert_eq!(
             enclosed("{input.status_code}"),
             Ok((
                 "",
-                Text::Expression(Expression {
-                    namespace: "input",
-                    field: "status_code"
-                })
+                Text::Expression(Expression::Variable(Variable {
+                    namespace: Namespace::Input,
+                    field: "status_code".to_string()
+                }))
             ))
         );
     }
@@ -116,36 +415,134 @@ mod tests {
     fn test_text() {
         assert_eq!(
             parse("hello {env.ttt}"),
-            Ok((
-                "",
-                vec![
-                    Text::Literal("hello "),
-                    Text::Expression(Expression {
-                        namespace: "env",
-                        field: "ttt"
-                    })
-                ]
-            ))
+            Ok(vec![
+                Text::Literal("hello ".to_string()),
+                Text::Expression(Expression::Variable(Variable {
+                    namespace: Namespace::Env,
+                    field: "ttt".to_string()
+                }))
+            ])
         );
 
         assert_eq!(
             parse("这是 Server 结果 {input.status_code}, 今天天气是 {input.text}。"),
-            Ok((
-                "",
+            Ok(vec![
+                Text::Literal("这是 Server 结果 ".to_string()),
+                Text::Expression(Expression::Variable(Variable {
+                    namespace: Namespace::Input,
+                    field: "status_code".to_string()
+                })),
+                Text::Literal(", 今天天气是 ".to_string()),
+                Text::Expression(Expression::Variable(Variable {
+                    namespace: Namespace::Input,
+                    field: "text".to_string()
+                })),
+                Text::Literal("。".to_string()),
+            ])
+        );
+
+        assert_eq!(parse(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse("hello {var.x}"),
+            Err(ParseError {
+                column: 8,
+                expected: "one of the namespaces input, env, vars, steps, error, secrets"
+                    .to_string(),
+                found: "`var`".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("结果 {input.text"),
+            Err(ParseError {
+                column: 15,
+                expected: "a closing brace `}`".to_string(),
+                found: "end of input".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("{env text}"),
+            Err(ParseError {
+                column: 5,
+                expected: "`.`".to_string(),
+                found: "whitespace".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("{input.}").unwrap_err().expected,
+            "a field name".to_string()
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let mut pipeline = Pipeline::default();
+        pipeline.set_var("greeting", "hello");
+        let mut context = Context::new(&pipeline).unwrap();
+        Arc::make_mut(&mut context.env).insert("name".to_string(), "world".to_string());
+        let mut input = Outputs::new();
+        input.insert("status_code", "200".to_string());
+        context.steps.insert("fetch".to_string(), input.clone());
+
+        let template =
+            Template::parse("{vars.greeting} {env.name}: { input.status_code }").unwrap();
+        assert_eq!(
+            template.render(&input, &context).unwrap(),
+            "hello world: 200"
+        );
+
+        let template = Template::parse("{steps.fetch.status_code}").unwrap();
+        assert_eq!(template.render(&input, &context).unwrap(), "200");
+
+        let template = Template::parse("{input.text}").unwrap();
+        assert!(template.render(&input, &context).is_err());
+    }
+
+    #[test]
+    fn test_call() {
+        assert_eq!(
+            parse("{now(\"%Y-%m-%d\", 'UTC')}-{ uuid() }"),
+            Ok(vec![
+                Text::Expression(Expression::Call(
+                    Function::Now,
+                    vec![
+                        Expression::Literal("%Y-%m-%d".to_string()),
+                        Expression::Literal("UTC".to_string())
+                    ]
+                )),
+                Text::Literal("-".to_string()),
+                Text::Expression(Expression::Call(Function::Uuid, vec![])),
+            ])
+        );
+        assert_eq!(
+            parse("{random(-1, env.max)}"),
+            Ok(vec![Text::Expression(Expression::Call(
+                Function::Random,
                 vec![
-                    Text::Literal("这是 Server 结果 "),
-                    Text::Expression(Expression {
-                        namespace: "input",
-                        field: "status_code"
-                    }),
-                    Text::Literal(", 今天天气是 "),
-                    Text::Expression(Expression {
-                        namespace: "input",
-                        field: "text"
-                    }),
-                    Text::Literal("。"),
+                    Expression::Literal("-1".to_string()),
+                    Expression::Variable(Variable {
+                        namespace: Namespace::Env,
+                        field: "max".to_string()
+                    })
                 ]
-            ))
+            ))])
+        );
+
+        let error = parse("{today()}").unwrap_err();
+        assert_eq!(error.column, 2);
+        assert_eq!(error.found, "`today`");
+        assert!(error.expected.starts_with("one of the functions now"));
+        assert_eq!(
+            parse("{random(1)}").unwrap_err().expected,
+            "arguments as in `random(min, max)`"
+        );
+        assert_eq!(parse("{random(1 2)}").unwrap_err().column, 11);
+        assert_eq!(
+            parse("{now(\"%Y)}").unwrap_err().expected,
+            "a closing quote `\"`"
         );
     }
 }
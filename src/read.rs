@@ -1,20 +1,25 @@
-use crate::{Context, Input, Inputs, Outputs, Workflow};
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType};
+use crate::{BlockingWorkflow, Context, Input, Inputs, Outputs};
 use anyhow::Result;
 use std::{fs::File, io::Read as _};
 
+#[derive(Clone)]
 pub struct Read {}
 
 impl Read {
+    const DESCRIPTION: &'static str = "Reads a file.";
+
     // Input
     const PATH: &'static str = "path";
-    const PARAMS: [&'static str; 1] = [Read::PATH];
+    const PARAMS: [ParameterSpec; 1] =
+        [ParameterSpec::new(Read::PATH, ParameterType::String, "File to read.").required()];
 
     const TEXT: &'static str = "text";
-    const OUTPUT: [&'static str; 1] = [Read::TEXT];
+    const OUTPUT: [OutputSpec; 1] = [OutputSpec::new(Read::TEXT, "Content of the file.")];
 }
 
-impl Workflow for Read {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()> {
+impl BlockingWorkflow for Read {
+    fn execute(&self, _context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
         let path = input.parameter(Read::PATH);
 
         let mut text = String::new();
@@ -22,16 +27,16 @@ impl Workflow for Read {
 
         let mut output = Outputs::new();
         output.insert(Read::TEXT, text);
-        if let Some(next) = context.next() {
-            next.execute(context, output)?;
-        }
-        Ok(())
+        Ok(vec![output])
     }
 
-    fn parameters(&self) -> &'static [&'static str] {
-        return &Read::PARAMS;
+    fn description(&self) -> &'static str {
+        Read::DESCRIPTION
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &Read::PARAMS
     }
-    fn outputs(&self) -> &'static [&'static str] {
-        return &Read::OUTPUT;
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &Read::OUTPUT
     }
 }
@@ -0,0 +1,150 @@
+use crate::error::ErrorKind;
+use crate::util::{deserialize_duration, deserialize_optional_duration};
+use crate::Outputs;
+use anyhow::{bail, Result};
+use rand::Rng;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// The longest delay between attempts, whatever the multiplier works out to.
+const MAX_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How a step is retried, e.g.
+///
+/// ```yaml
+/// retry:
+///   attempts: 4
+///   initial_delay: 500ms
+///   multiplier: 2
+///   jitter: 0.2
+///   on: [network, io]
+///   status_codes: [502, 503, 504]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Retry {
+    /// Total number of attempts, including the first one.
+    #[serde(default = "Retry::default_attempts")]
+    pub attempts: u32,
+    #[serde(
+        default = "Retry::default_initial_delay",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub initial_delay: Duration,
+    #[serde(default = "Retry::default_multiplier")]
+    pub multiplier: f64,
+    #[serde(default, deserialize_with = "deserialize_optional_duration")]
+    pub max_delay: Option<Duration>,
+    /// Randomises each delay by up to this fraction in either direction.
+    #[serde(default)]
+    pub jitter: f64,
+    /// Error types worth another attempt.
+    #[serde(default = "Retry::default_on")]
+    pub on: Vec<ErrorKind>,
+    /// Response codes in a `status_code` output that count as a failed attempt.
+    #[serde(default)]
+    pub status_codes: Vec<u16>,
+}
+
+impl Retry {
+    fn default_attempts() -> u32 {
+        3
+    }
+
+    fn default_initial_delay() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn default_multiplier() -> f64 {
+        2.0
+    }
+
+    fn default_on() -> Vec<ErrorKind> {
+        vec![ErrorKind::Network]
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.attempts == 0 {
+            bail!("retry.attempts must be at least 1.");
+        }
+        if !(1.0..=f64::MAX).contains(&self.multiplier) {
+            bail!("retry.multiplier must be a number of at least 1.");
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            bail!("retry.jitter must be between 0 and 1.");
+        }
+        Ok(())
+    }
+
+    /// Whether the outcome of an attempt calls for another one.
+    pub fn should_retry(&self, result: &Result<Vec<Outputs>>) -> bool {
+        match result {
+            Err(error) => self.on.contains(&ErrorKind::of(error)),
+            Ok(outputs) => outputs.iter().any(|output| {
+                output
+                    .get("status_code")
+                    .and_then(|code| code.parse().ok())
+                    .is_some_and(|code| self.status_codes.contains(&code))
+            }),
+        }
+    }
+
+    /// The delay before the attempt following `attempt`, which counts from 1, at most a day.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let mut delay = self.initial_delay.as_secs_f64() * self.multiplier.powi(attempt as i32 - 1);
+        if let Some(max_delay) = self.max_delay {
+            delay = delay.min(max_delay.as_secs_f64());
+        }
+        if self.jitter > 0.0 {
+            delay *= 1.0 + rand::thread_rng().gen_range(-self.jitter..=self.jitter);
+        }
+        Duration::try_from_secs_f64(delay.max(0.0))
+            .unwrap_or(MAX_DELAY)
+            .min(MAX_DELAY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn retry(yaml: &str) -> Retry {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_delay() {
+        let retry = retry("{initial_delay: 100ms, multiplier: 3, max_delay: 1s}");
+        assert_eq!(retry.attempts, 3);
+        assert_eq!(retry.delay(1), Duration::from_millis(100));
+        assert_eq!(retry.delay(2), Duration::from_millis(300));
+        assert_eq!(retry.delay(3), Duration::from_millis(900));
+        assert_eq!(retry.delay(4), Duration::from_secs(1));
+
+        let retry = self::retry("{multiplier: 1e30}");
+        assert!(retry.validate().is_ok());
+        assert_eq!(retry.delay(3), MAX_DELAY);
+        assert!(self::retry("{multiplier: .inf}").validate().is_err());
+
+        let retry = self::retry("{initial_delay: 1s, jitter: 0.5}");
+        for _ in 0..10 {
+            let delay = retry.delay(1);
+            assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1500));
+        }
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let retry = retry("{on: [io], status_codes: [503]}");
+        let io = std::io::Error::other("disk");
+        assert!(retry.should_retry(&Err(io.into())));
+        assert!(!retry.should_retry(&Err(anyhow!("bad"))));
+
+        let mut output = Outputs::new();
+        output.insert("status_code", "503".to_string());
+        assert!(retry.should_retry(&Ok(vec![output.clone()])));
+        output.insert("status_code", "200".to_string());
+        assert!(!retry.should_retry(&Ok(vec![output])));
+    }
+}
@@ -1,41 +1,50 @@
-use crate::{Context, Input, Inputs, Outputs, Workflow};
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType};
+use crate::{BlockingWorkflow, Context, Input, Inputs, Outputs};
 use anyhow::Result;
 use chrono::{DateTime, Duration, Local};
 use rss::Channel;
 use std::io::BufReader;
 
+#[derive(Clone)]
 pub struct Rss {}
 
 impl Rss {
+    const DESCRIPTION: &'static str = "Parses an RSS feed into one branch per item.";
+
     // Input
     const TEXT: &'static str = "text";
     const SCHEDULE_IN_SECS: &'static str = "schedule_in_secs";
-    const PARAMS: [&'static str; 2] = [Rss::TEXT, Rss::SCHEDULE_IN_SECS];
+    const PARAMS: [ParameterSpec; 2] = [
+        ParameterSpec::new(Rss::TEXT, ParameterType::String, "RSS document.").required(),
+        ParameterSpec::new(
+            Rss::SCHEDULE_IN_SECS,
+            ParameterType::Int,
+            "Only keep items published within this many seconds.",
+        ),
+    ];
 
     // Output
     const TITLE: &'static str = "title";
     const LINK: &'static str = "link";
-    const OUTPUT: [&'static str; 2] = [Rss::TITLE, Rss::LINK];
+    const OUTPUT: [OutputSpec; 2] = [
+        OutputSpec::new(Rss::TITLE, "Title of the item."),
+        OutputSpec::new(Rss::LINK, "Address the item links to."),
+    ];
 }
 
-impl Workflow for Rss {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()> {
+impl BlockingWorkflow for Rss {
+    fn execute(&self, _context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
         let text = input.parameter(Rss::TEXT);
         let after = input
-            .parameter(Rss::SCHEDULE_IN_SECS)
-            .parse()
+            .int(Rss::SCHEDULE_IN_SECS)
             .map(|secs| Local::now() - Duration::seconds(secs));
 
-        let next = match context.next() {
-            Some(next) => next,
-            None => return Ok(()),
-        };
         let channel = Channel::read_from(BufReader::new(text.as_bytes()))?;
+        let mut outputs = Vec::new();
         for item in channel.items() {
-            if let (Ok(after), Some(Ok(pub_date))) = (
-                &after,
-                item.pub_date().map(|s| DateTime::parse_from_rfc2822(s)),
-            ) {
+            if let (Some(after), Some(Ok(pub_date))) =
+                (&after, item.pub_date().map(DateTime::parse_from_rfc2822))
+            {
                 if &pub_date < after {
                     break;
                 }
@@ -49,16 +58,19 @@ impl Workflow for Rss {
                 Rss::LINK,
                 item.link().map(str::to_string).unwrap_or_default(),
             );
-            next.execute(context, output)?;
+            outputs.push(output);
         }
 
-        Ok(())
+        Ok(outputs)
     }
 
-    fn parameters(&self) -> &'static [&'static str] {
-        return &Rss::PARAMS;
+    fn description(&self) -> &'static str {
+        Rss::DESCRIPTION
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &Rss::PARAMS
     }
-    fn outputs(&self) -> &'static [&'static str] {
-        return &Rss::OUTPUT;
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &Rss::OUTPUT
     }
 }
@@ -1,37 +1,49 @@
-use crate::{Context, Input, Inputs, Workflow};
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType};
+use crate::{BlockingWorkflow, Context, Input, Inputs, Outputs};
 use anyhow::Result;
+use std::fs::File;
 use std::io::Write;
-use std::{collections::HashMap, fs::File};
 
+#[derive(Clone)]
 pub struct Save {}
 
 impl Save {
+    const DESCRIPTION: &'static str = "Writes text to a file.";
+
     // Input
     const TEXT: &'static str = "text";
     const DESTINATION: &'static str = "destination";
-    const PARAMS: [&'static str; 2] = [Save::TEXT, Save::DESTINATION];
+    const PARAMS: [ParameterSpec; 2] = [
+        ParameterSpec::new(Save::TEXT, ParameterType::String, "Content to write.").required(),
+        ParameterSpec::new(
+            Save::DESTINATION,
+            ParameterType::String,
+            "File to write to.",
+        )
+        .required(),
+    ];
 
-    const OUTPUT: [&'static str; 0] = [];
+    const OUTPUT: [OutputSpec; 0] = [];
 }
 
-impl Workflow for Save {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()> {
+impl BlockingWorkflow for Save {
+    fn execute(&self, _context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
         let text = input.parameter(Save::TEXT);
         let destination = input.parameter(Save::DESTINATION);
 
         let mut file = File::create(destination)?;
         file.write_all(text.as_bytes())?;
 
-        if let Some(next) = context.next() {
-            next.execute(context, HashMap::new())?;
-        }
-        Ok(())
+        Ok(vec![Outputs::new()])
     }
 
-    fn parameters(&self) -> &'static [&'static str] {
-        return &Save::PARAMS;
+    fn description(&self) -> &'static str {
+        Save::DESCRIPTION
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &Save::PARAMS
     }
-    fn outputs(&self) -> &'static [&'static str] {
-        return &Save::OUTPUT;
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &Save::OUTPUT
     }
 }
@@ -0,0 +1,327 @@
+use crate::error::ErrorKind;
+use crate::parameter::{ParameterSpec, ParameterType};
+use crate::{Workflow, WORKFLOWS};
+use serde_json::{json, Map, Value};
+use strum::IntoEnumIterator;
+
+const SCALAR: [&str; 3] = ["string", "number", "boolean"];
+
+/// A JSON Schema for configuration files, generated from the step registry so editors can
+/// complete and check step types and their parameters.
+pub fn schema() -> Value {
+    let mut types: Vec<_> = WORKFLOWS.keys().copied().collect();
+    types.sort_unstable();
+    let variants: Vec<_> = types
+        .iter()
+        .map(|workflow_type| {
+            let workflow = &WORKFLOWS[workflow_type];
+            json!({
+                "if": { "properties": { "type": { "const": workflow_type } } },
+                "then": {
+                    "description": workflow.description(),
+                    "properties": { "parameters": parameters(workflow.parameters()) },
+                },
+            })
+        })
+        .collect();
+    let kinds: Vec<_> = ErrorKind::iter().map(|kind| kind.to_string()).collect();
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "workflows configuration",
+        "type": "object",
+        // Included files may bring every job; `workflows` and `jobs` exclude each other.
+        "anyOf": [
+            { "required": ["workflows"] },
+            { "required": ["jobs"] },
+            { "required": ["include"] },
+        ],
+        "not": { "required": ["workflows", "jobs"] },
+        "additionalProperties": false,
+        "properties": {
+            "workflows": { "type": "array", "items": { "$ref": "#/definitions/entry" } },
+            "jobs": {
+                "description": "Named pipelines, which take the other settings as defaults.",
+                "type": "object",
+                "additionalProperties": { "$ref": "#/definitions/job" },
+            },
+            "on_failure": {
+                "description": "Steps run when the pipeline fails, with `error.message`, `error.step` and `error.type`.",
+                "type": "array",
+                "items": { "$ref": "#/definitions/entry" },
+            },
+            "timezone": {
+                "description": "An IANA name such as `Asia/Shanghai`; time functions use local time when absent.",
+                "type": "string",
+            },
+            "vars": {
+                "description": "Values shared by every step as `{vars.name}`.",
+                "type": "object",
+                "additionalProperties": { "type": SCALAR },
+            },
+            "secrets": {
+                "description": "Values shared by every step as `{secrets.name}`, masked wherever they are printed.",
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "minProperties": 1,
+                    "maxProperties": 1,
+                    "additionalProperties": false,
+                    "properties": {
+                        "env": { "description": "An environment variable.", "type": "string" },
+                        "file": { "description": "A file, relative to this one.", "type": "string" },
+                        "store": { "description": "An entry of the `secret_store`.", "type": "string" },
+                    },
+                },
+            },
+            "secret_store": {
+                "description": "A file written by `workflows secret`, opened with the key in `WORKFLOWS_STORE_KEY`.",
+                "type": "string",
+            },
+            "timeout": { "$ref": "#/definitions/duration" },
+            "triggers": {
+                "description": "Starts the `workflows` list; each job has its own instead.",
+                "$ref": "#/definitions/triggers",
+            },
+            "include": {
+                "description": "Files whose jobs and vars are added, relative to this one.",
+                "type": "array",
+                "items": {
+                    "anyOf": [
+                        { "type": "string" },
+                        {
+                            "type": "object",
+                            "required": ["path"],
+                            "additionalProperties": false,
+                            "properties": { "path": { "type": "string" }, "with": { "$ref": "#/definitions/with" } },
+                        },
+                    ],
+                },
+            },
+            "concurrency": {
+                "description": "How many steps run at once across the pipeline, and the default for fan-outs.",
+                "$ref": "#/definitions/concurrency",
+            },
+            "strict": {
+                "description": "Fails steps that emit other outputs than they declare.",
+                "type": "boolean",
+            },
+        },
+        "definitions": {
+            "job": {
+                "type": "object",
+                "required": ["steps"],
+                "additionalProperties": false,
+                "properties": {
+                    "steps": { "type": "array", "items": { "$ref": "#/definitions/entry" } },
+                    "vars": {
+                        "description": "Added to the shared vars, replacing those with the same name.",
+                        "type": "object",
+                        "additionalProperties": { "type": SCALAR },
+                    },
+                    "triggers": { "$ref": "#/definitions/triggers" },
+                    "on_failure": { "type": "array", "items": { "$ref": "#/definitions/entry" } },
+                    "timeout": { "$ref": "#/definitions/duration" },
+                    "concurrency": { "$ref": "#/definitions/concurrency" },
+                },
+            },
+            "triggers": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "schedule": {
+                        "description": "A cron expression; without the seconds field, weekdays count from 0 for Sunday as in crontab.",
+                        "type": "string",
+                    },
+                    "timezone": {
+                        "description": "The zone the schedule is read in, instead of the configuration's timezone.",
+                        "type": "string",
+                    },
+                    "catch_up": {
+                        "description": "What `workflows daemon` does about the runs it missed while it was down.",
+                        "enum": ["skip", "once", "all"],
+                    },
+                    "webhook": {
+                        "description": "Lets `workflows serve` start the job from HTTP requests.",
+                        "type": "object",
+                        "required": ["path"],
+                        "additionalProperties": false,
+                        "properties": {
+                            "path": { "type": "string", "pattern": "^/" },
+                            "secret": {
+                                "description": "Checks each request's `X-Hub-Signature-256`; may read `env`.",
+                                "type": "string",
+                            },
+                        },
+                    },
+                    "watch": {
+                        "description": "Lets `workflows daemon` start the job when files change.",
+                        "type": "object",
+                        "required": ["paths"],
+                        "additionalProperties": false,
+                        "properties": {
+                            "paths": {
+                                "description": "Files or glob patterns, relative to the configuration.",
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "minItems": 1,
+                            },
+                            "debounce": { "$ref": "#/definitions/duration" },
+                        },
+                    },
+                },
+            },
+            "entry": {
+                "anyOf": [{ "$ref": "#/definitions/step" }, { "$ref": "#/definitions/uses" }],
+            },
+            "uses": {
+                "description": "Replaced by the steps of another file, relative to this one.",
+                "type": "object",
+                "required": ["uses"],
+                "additionalProperties": false,
+                "properties": {
+                    "uses": { "type": "string" },
+                    "with": { "$ref": "#/definitions/with" },
+                },
+            },
+            "with": {
+                "description": "Values the included file reads as `{with.name}`.",
+                "type": "object",
+                "additionalProperties": { "type": SCALAR },
+            },
+            "concurrency": {
+                "description": "How many branches of a fanned-out step run at once.",
+                "type": "integer",
+                "minimum": 1,
+            },
+            "duration": {
+                "description": "Such as `500ms`, `1.5s`, `10m` or `2h`; a bare number means seconds.",
+                "type": ["string", "number"],
+                "pattern": r"^\s*\d+(\.\d+)?\s*(ms|s|m|h)?\s*$",
+            },
+            "retry": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "attempts": { "type": "integer", "minimum": 1, "default": 3 },
+                    "initial_delay": { "$ref": "#/definitions/duration" },
+                    "multiplier": { "type": "number", "minimum": 1, "default": 2 },
+                    "max_delay": { "$ref": "#/definitions/duration" },
+                    "jitter": { "type": "number", "minimum": 0, "maximum": 1 },
+                    "on": { "type": "array", "items": { "enum": kinds } },
+                    "status_codes": {
+                        "type": "array",
+                        "items": { "type": "integer", "minimum": 100, "maximum": 599 },
+                    },
+                },
+            },
+            "step": {
+                "type": "object",
+                "required": ["type"],
+                "additionalProperties": false,
+                "properties": {
+                    "type": { "enum": types },
+                    "id": {
+                        "description": "Lets later steps read this step's outputs as `{steps.<id>.<output>}`.",
+                        "type": "string",
+                        "pattern": r"^[\w-]+$",
+                    },
+                    "needs": {
+                        "description": "Ids of the steps to run first, whose outputs are joined into `input`; `[]` reads the pipeline's input.",
+                        "type": "array",
+                        "items": { "type": "string" },
+                    },
+                    "parameters": { "type": "object" },
+                    "if": {
+                        "description": "Runs the step only when the condition holds, e.g. `input.status_code != 200`.",
+                        "type": "string",
+                    },
+                    "on_false": { "enum": ["continue", "stop"] },
+                    "continue_on_error": { "type": "boolean" },
+                    "retry": { "$ref": "#/definitions/retry" },
+                    "timeout": { "$ref": "#/definitions/duration" },
+                    "concurrency": { "$ref": "#/definitions/concurrency" },
+                },
+                "allOf": variants,
+            },
+        },
+    })
+}
+
+/// The `parameters` object of one step type. Typed values may also be templates.
+fn parameters(specs: &[ParameterSpec]) -> Value {
+    let mut properties = Map::new();
+    for spec in specs {
+        let mut property = match spec.kind {
+            ParameterType::String => json!({ "type": SCALAR }),
+            ParameterType::Int => json!({ "type": ["integer", "string"] }),
+            ParameterType::Bool => json!({ "type": ["boolean", "string"] }),
+            ParameterType::List => json!({ "type": "array", "items": { "type": SCALAR } }),
+            ParameterType::Map => json!({
+                "type": "object",
+                "additionalProperties": { "type": SCALAR },
+            }),
+        };
+        property["description"] = json!(spec.description);
+        if let Some(default) = spec.default {
+            property["default"] = json!(default);
+        }
+        properties.insert(spec.name.to_string(), property);
+    }
+    let required: Vec<_> = specs
+        .iter()
+        .filter(|spec| spec.required)
+        .map(|spec| spec.name)
+        .collect();
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "required": required,
+        "properties": properties,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema() {
+        let schema = schema();
+        let step = &schema["definitions"]["step"];
+        assert_eq!(
+            step["properties"]["type"]["enum"].as_array().unwrap().len(),
+            WORKFLOWS.len()
+        );
+        let http = step["allOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|variant| variant["if"]["properties"]["type"]["const"] == "http")
+            .unwrap();
+        let parameters = &http["then"]["properties"]["parameters"];
+        assert_eq!(parameters["required"], json!(["url"]));
+        assert_eq!(parameters["properties"]["method"]["default"], "GET");
+        assert_eq!(parameters["properties"]["url"]["type"], json!(SCALAR));
+        assert_eq!(
+            schema["definitions"]["retry"]["properties"]["on"]["items"]["enum"][0],
+            "template"
+        );
+
+        // Checks the top-level rules on which keys a configuration has.
+        let admits = |keys: &[&str]| {
+            let has = |rule: &Value| {
+                let required = rule["required"].as_array().unwrap();
+                required
+                    .iter()
+                    .all(|key| keys.contains(&key.as_str().unwrap()))
+            };
+            schema["anyOf"].as_array().unwrap().iter().any(has) && !has(&schema["not"])
+        };
+        assert!(admits(&["workflows"]));
+        assert!(admits(&["jobs", "include"]));
+        assert!(admits(&["include", "vars"]));
+        assert!(!admits(&["workflows", "jobs"]));
+        assert!(!admits(&["vars"]));
+    }
+}
@@ -0,0 +1,314 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use anyhow::{anyhow, bail, Context as _, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::RwLock,
+    thread::{self, JoinHandle},
+};
+
+/// What secret values are replaced with wherever the program prints.
+pub const MASK: &str = "***";
+/// Holds the key of the `secret_store`: 32 bytes in base64.
+pub const KEY_VAR: &str = "WORKFLOWS_STORE_KEY";
+const NONCE_SIZE: usize = 12;
+
+lazy_static! {
+    /// Every secret value loaded so far, the longest first.
+    static ref SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+}
+
+/// Where an entry of `secrets` is read from.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum Source {
+    /// An environment variable.
+    Env(String),
+    /// A file, relative to the configuration; a final line break is dropped.
+    File(String),
+    /// An entry of the encrypted `secret_store`.
+    Store(String),
+}
+
+/// Fails when a secret reads the store but the configuration names none.
+pub fn check(secrets: &BTreeMap<String, Source>, store: Option<&str>) -> Result<()> {
+    for (name, source) in secrets {
+        if let (Source::Store(_), None) = (source, store) {
+            bail!(
+                "secrets.{} reads the secret store, but no secret_store is given.",
+                name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Reads the declared secrets, with paths relative to `base`, and masks their values from
+/// then on.
+pub fn load(
+    secrets: &BTreeMap<String, Source>,
+    store: Option<&str>,
+    base: &Path,
+) -> Result<HashMap<String, String>> {
+    check(secrets, store)?;
+    let mut stored = None;
+    let mut values = HashMap::new();
+    for (name, source) in secrets {
+        let value = match source {
+            Source::Env(var) => env::var(var)
+                .with_context(|| format!("secrets.{} reads {}, which is not set.", name, var))?,
+            Source::File(path) => {
+                let path = base.join(path);
+                let mut value = fs::read_to_string(&path).with_context(|| {
+                    format!("Unable to read secrets.{} from {}.", name, path.display())
+                })?;
+                trim_line_break(&mut value);
+                value
+            }
+            Source::Store(entry) => {
+                if stored.is_none() {
+                    let path = base.join(store.expect("Checked above."));
+                    stored = Some(Store::open(&path)?.read()?);
+                }
+                let entries = stored.as_ref().expect("The store was just read.");
+                entries
+                    .get(entry)
+                    .with_context(|| {
+                        format!("secrets.{} reads {}, which is not stored.", name, entry)
+                    })?
+                    .clone()
+            }
+        };
+        register(&value);
+        values.insert(name.clone(), value);
+    }
+    Ok(values)
+}
+
+fn trim_line_break(value: &mut String) {
+    if value.ends_with('\n') {
+        value.pop();
+        if value.ends_with('\r') {
+            value.pop();
+        }
+    }
+}
+
+/// Masks `value` in everything printed from now on. Under GitHub Actions the runner is told
+/// to mask it as well, in case it reaches the job log some other way.
+pub fn register(value: &str) {
+    if value.is_empty() {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap();
+    if secrets.iter().any(|secret| secret == value) {
+        return;
+    }
+    // A value containing another is masked whole.
+    let index = secrets
+        .iter()
+        .position(|secret| secret.len() < value.len())
+        .unwrap_or(secrets.len());
+    secrets.insert(index, value.to_string());
+    if env::var("GITHUB_ACTIONS").as_deref() == Ok("true") {
+        for line in value.lines().filter(|line| !line.trim().is_empty()) {
+            println!("::add-mask::{}", line);
+        }
+    }
+}
+
+/// Whether any secret has been loaded.
+pub fn any() -> bool {
+    !SECRETS.read().unwrap().is_empty()
+}
+
+/// `text` with every secret value replaced by `***`.
+pub fn mask(text: &str) -> Cow<'_, str> {
+    let mut masked = Cow::Borrowed(text);
+    for secret in SECRETS.read().unwrap().iter() {
+        if masked.contains(&secret[..]) {
+            masked = Cow::Owned(masked.replace(&secret[..], MASK));
+        }
+    }
+    masked
+}
+
+/// Masks what is written through it, such as logs.
+pub struct Masked<W>(pub W);
+
+impl<W: Write> Write for Masked<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .write_all(mask(&String::from_utf8_lossy(buf)).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Copies `reader` to `writer` line by line with secrets masked, until it ends.
+pub fn forward(
+    reader: impl Read + Send + 'static,
+    writer: impl Write + Send + 'static,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut writer = Masked(writer);
+        for line in BufReader::new(reader).split(b'\n') {
+            let mut line = match line {
+                Ok(line) => line,
+                Err(_) => return,
+            };
+            line.push(b'\n');
+            if writer
+                .write_all(&line)
+                .and_then(|_| writer.flush())
+                .is_err()
+            {
+                return;
+            }
+        }
+    })
+}
+
+/// A file of named secrets, encrypted with AES-256-GCM under the key in `WORKFLOWS_STORE_KEY`.
+pub struct Store {
+    path: PathBuf,
+    cipher: Aes256Gcm,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self> {
+        let key = env::var(KEY_VAR).with_context(|| {
+            format!(
+                "Set {} to open {}; `openssl rand -base64 32` makes a key.",
+                KEY_VAR,
+                path.display()
+            )
+        })?;
+        Store::with_key(path, &key)
+    }
+
+    fn with_key(path: &Path, key: &str) -> Result<Self> {
+        let key = STANDARD
+            .decode(key.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .with_context(|| format!("{} should be 32 bytes in base64.", KEY_VAR))?;
+        Ok(Store {
+            path: path.to_path_buf(),
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        })
+    }
+
+    /// The stored entries; none while the file does not exist.
+    pub fn read(&self) -> Result<BTreeMap<String, String>> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("Unable to read {}.", self.path.display()))
+            }
+        };
+        let invalid = || {
+            anyhow!(
+                "{} is not a secret store, or {} does not open it.",
+                self.path.display(),
+                KEY_VAR
+            )
+        };
+        let sealed = STANDARD.decode(text.trim()).map_err(|_| invalid())?;
+        if sealed.len() < NONCE_SIZE {
+            return Err(invalid());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+        let plain = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| invalid())?;
+        serde_json::from_slice(&plain).map_err(|_| invalid())
+    }
+
+    /// Adds or replaces an entry, encrypting the whole file anew.
+    pub fn set(&self, name: &str, value: &str) -> Result<()> {
+        let mut entries = self.read()?;
+        entries.insert(name.to_string(), value.to_string());
+        let plain = serde_json::to_vec(&entries)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, &plain[..])
+            .map_err(|_| anyhow!("Unable to encrypt {}.", self.path.display()))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, STANDARD.encode(sealed) + "\n")
+            .and_then(|_| fs::rename(&temporary, &self.path))
+            .with_context(|| format!("Unable to save {}.", self.path.display()))
+    }
+}
+
+/// Stores what standard input holds as `name`, for `workflows secret`.
+pub fn store_from_stdin(path: &str, name: &str) -> Result<()> {
+    let mut value = String::new();
+    io::stdin()
+        .read_to_string(&mut value)
+        .context("Unable to read the secret from standard input.")?;
+    trim_line_break(&mut value);
+    if value.is_empty() {
+        bail!("Standard input holds no secret for {}.", name);
+    }
+    Store::open(Path::new(path))?.set(name, &value)?;
+    println!("Stored {} in {}.", name, path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stand_in::TempDir;
+
+    #[test]
+    fn test_store() {
+        let dir = TempDir::new("store");
+        let path = dir.join("secrets.store");
+        let key = STANDARD.encode([7; 32]);
+        let store = Store::with_key(&path, &key).unwrap();
+        assert!(store.read().unwrap().is_empty());
+        store.set("token", "t0ken").unwrap();
+        store.set("secret", "s3cret").unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("t0ken"));
+        let entries = Store::with_key(&path, &key).unwrap().read().unwrap();
+        assert_eq!(entries["token"], "t0ken");
+        assert_eq!(entries["secret"], "s3cret");
+
+        let other = Store::with_key(&path, &STANDARD.encode([8; 32])).unwrap();
+        let error = other.read().unwrap_err().to_string();
+        assert!(error.ends_with("does not open it."), "{}", error);
+        assert!(Store::with_key(&path, "c2hvcnQ=").is_err());
+    }
+
+    #[test]
+    fn test_mask() {
+        register("pa55");
+        register("pa55word");
+        assert_eq!(mask("user:pa55word pin:pa55"), "user:*** pin:***");
+        assert!(matches!(mask("nothing here"), Cow::Borrowed(_)));
+
+        let mut masked = Masked(Vec::new());
+        write!(masked, "token=pa55").unwrap();
+        assert_eq!(masked.0, b"token=***");
+    }
+}
@@ -0,0 +1,96 @@
+//! A local HTTP server that stands in for the remote APIs in tests.
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    ops::Deref,
+    path::{Path, PathBuf},
+    process, thread,
+};
+
+/// A directory for a test's files, removed when dropped even if the test fails.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates `workflows_<name>_<pid>` in the system's temporary directory.
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("workflows_{}_{}", name, process::id()));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// What the stand-in saw of a request.
+pub struct Request {
+    pub method: String,
+    /// The path with its query string.
+    pub path: String,
+    pub body: String,
+}
+
+/// Serves every request with the status and JSON body `respond` returns, on a background
+/// thread, and returns the server's root URL.
+pub fn serve(respond: fn(&Request) -> (u16, String)) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let _ = answer(stream, respond);
+        }
+    });
+    url
+}
+
+fn answer(stream: TcpStream, respond: fn(&Request) -> (u16, String)) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let request = Request {
+        method,
+        path,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    };
+    let (status, body) = respond(&request);
+    write!(
+        &stream,
+        "HTTP/1.1 {} Stand-in\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,262 @@
+use crate::parser::{Namespace, Template};
+use crate::{function, util, watch, webhook};
+use anyhow::{bail, Context as _, Result};
+use chrono_tz::Tz;
+use cron::Schedule;
+use glob::Pattern;
+use serde::Deserialize;
+use std::{str::FromStr, time::Duration};
+
+/// What starts a job without `workflows run`, e.g.
+///
+/// ```yaml
+/// triggers:
+///   schedule: "*/30 * * * *"
+///   timezone: Asia/Shanghai
+///   catch_up: once
+///   webhook:
+///     path: /github
+///     secret: "{secrets.github_webhook}"
+///   watch:
+///     paths: [config.yml, "notes/**/*.md"]
+///     debounce: 1s
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Triggers {
+    /// A cron expression; without the seconds field, weekdays count from 0 for Sunday as in crontab.
+    pub schedule: Option<String>,
+    /// The zone the schedule is read in, instead of the configuration's `timezone`.
+    pub timezone: Option<String>,
+    /// What `workflows daemon` does about the runs it missed while it was down.
+    #[serde(default)]
+    pub catch_up: CatchUp,
+    pub webhook: Option<Webhook>,
+    pub watch: Option<Watch>,
+}
+
+/// Lets `workflows daemon` start the job when files change.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Watch {
+    /// Files or glob patterns, relative to the configuration.
+    pub paths: Vec<String>,
+    /// How long changes have to settle before the job starts.
+    #[serde(
+        default = "Watch::default_debounce",
+        deserialize_with = "util::deserialize_duration"
+    )]
+    pub debounce: Duration,
+}
+
+/// Lets `workflows serve` start the job from HTTP requests.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    /// The route, such as `/github`.
+    pub path: String,
+    /// Checks each request's `X-Hub-Signature-256` against this key; a template that may
+    /// read `env` and `secrets`.
+    pub secret: Option<String>,
+}
+
+/// How many of the runs missed since the last recorded one are made up at start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CatchUp {
+    /// None; wait for the next scheduled time.
+    #[default]
+    Skip,
+    /// One run for all of them.
+    Once,
+    /// Every one, in order.
+    All,
+}
+
+impl Triggers {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(schedule) = &self.schedule {
+            parse_schedule(schedule)?;
+        }
+        self.compile_timezone()?;
+        if let Some(webhook) = &self.webhook {
+            webhook.validate()?;
+        }
+        if let Some(watch) = &self.watch {
+            watch.validate()?;
+        }
+        Ok(())
+    }
+
+    /// What the first step of a job started by these triggers finds in `input`.
+    pub fn inputs(&self) -> Vec<&'static str> {
+        let mut inputs = Vec::new();
+        if self.webhook.is_some() {
+            inputs.extend(webhook::FIELDS);
+        }
+        if self.watch.is_some() {
+            inputs.extend(watch::FIELDS);
+        }
+        inputs
+    }
+
+    pub fn compile_timezone(&self) -> Result<Option<Tz>> {
+        self.timezone
+            .as_deref()
+            .map(function::parse_timezone)
+            .transpose()
+    }
+}
+
+impl Webhook {
+    pub fn validate(&self) -> Result<()> {
+        if !self.path.starts_with('/') {
+            bail!("The webhook path `{}` should start with /.", self.path);
+        }
+        if let Some(secret) = &self.secret {
+            self.compile_secret()?;
+            if secret.is_empty() {
+                bail!("The webhook secret is empty.");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn compile_secret(&self) -> Result<Option<Template>> {
+        let raw = match &self.secret {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        let template = Template::parse(raw).context("Invalid template in the webhook secret.")?;
+        let variables = template.variables();
+        if variables
+            .iter()
+            .any(|(namespace, _)| !matches!(namespace, Namespace::Env | Namespace::Secrets))
+        {
+            bail!("The webhook secret can only use env, secrets and functions.");
+        }
+        Ok(Some(template))
+    }
+}
+
+impl Watch {
+    fn default_debounce() -> Duration {
+        Duration::from_millis(500)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.paths.is_empty() {
+            bail!("The watch trigger has no paths.");
+        }
+        for path in &self.paths {
+            Pattern::new(path).with_context(|| format!("Invalid watch path `{}`.", path))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a cron expression, reading five fields as minute to weekday at second zero, with
+/// weekdays numbered as crontab does: 0 or 7 for Sunday.
+pub fn parse_schedule(raw: &str) -> Result<Schedule> {
+    let fields: Vec<_> = raw.split_whitespace().collect();
+    let expression = match &fields[..] {
+        [minute, hour, day, month, weekday] => {
+            let weekday =
+                crontab_weekdays(weekday).with_context(|| format!("Invalid schedule `{}`", raw))?;
+            format!("0 {} {} {} {} {}", minute, hour, day, month, weekday)
+        }
+        _ => raw.to_string(),
+    };
+    Schedule::from_str(&expression).with_context(|| format!("Invalid schedule `{}`", raw))
+}
+
+/// Renumbers the numeric weekdays of a crontab field from 0–7 starting on Sunday to the
+/// 1–7 of seven-field expressions, which also start on Sunday. Names pass through.
+fn crontab_weekdays(field: &str) -> Result<String> {
+    let day = |raw: &str| -> Result<u32> {
+        match raw.parse() {
+            Ok(day) if day <= 7 => Ok(day),
+            _ => bail!("Weekday {} should be between 0 and 7.", raw),
+        }
+    };
+    let mut items = Vec::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (item, None),
+        };
+        let numeric = |raw: &str| raw.chars().all(|c| c.is_ascii_digit()) && !raw.is_empty();
+        let renumbered = match range.split_once('-') {
+            Some((first, last)) if numeric(first) && numeric(last) => {
+                let (first, last) = (day(first)? % 7, day(last)?);
+                match (last, step) {
+                    _ if first > last => bail!("Weekday range {} runs backwards.", range),
+                    // Saturday to Sunday wraps around the end of the week.
+                    (7, None) if first > 0 => format!("{}-7,1", first + 1),
+                    (7, Some(_)) if first > 0 => {
+                        bail!("Weekday range {} with a step should end on 6.", range)
+                    }
+                    (7, _) => "1-7".to_string(),
+                    _ => format!("{}-{}", first + 1, last + 1),
+                }
+            }
+            None if numeric(range) => (day(range)? % 7 + 1).to_string(),
+            _ => range.to_string(),
+        };
+        items.push(match step {
+            Some(step) => format!("{}/{}", renumbered, step),
+            None => renumbered,
+        });
+    }
+    Ok(items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone, Utc, Weekday};
+
+    #[test]
+    fn test_parse_schedule() {
+        assert!(parse_schedule("*/30 * * * *").is_ok());
+        assert!(parse_schedule("0 0 9 * * Mon-Fri").is_ok());
+        assert!(parse_schedule("every hour").is_err());
+        let error = parse_schedule("0 9 * * 8").unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "Invalid schedule `0 9 * * 8`: Weekday 8 should be between 0 and 7."
+        );
+
+        // Saturday, 17 October 2026.
+        let saturday = Utc.with_ymd_and_hms(2026, 10, 17, 10, 0, 0).unwrap();
+        let weekdays = |raw: &str| -> Vec<Weekday> {
+            let schedule = parse_schedule(raw).unwrap();
+            schedule
+                .after(&saturday)
+                .take(7)
+                .map(|time| time.weekday())
+                .collect()
+        };
+        let working = weekdays("0 9 * * 1-5");
+        assert_eq!(working[0], Weekday::Mon);
+        assert!(working.contains(&Weekday::Fri));
+        assert!(!working.contains(&Weekday::Sat) && !working.contains(&Weekday::Sun));
+        assert!(weekdays("0 9 * * 0").iter().all(|day| *day == Weekday::Sun));
+        assert!(weekdays("0 9 * * 7").iter().all(|day| *day == Weekday::Sun));
+        assert_eq!(
+            &weekdays("0 9 * * 5-7")[..3],
+            [Weekday::Sun, Weekday::Fri, Weekday::Sat]
+        );
+        assert_eq!(weekdays("0 9 * * Mon")[0], Weekday::Mon);
+    }
+
+    #[test]
+    fn test_watch() {
+        let triggers: Triggers = serde_yaml::from_str("watch:\n  paths: [a.yml]\n").unwrap();
+        let watch = triggers.watch.as_ref().unwrap();
+        assert_eq!(watch.debounce, Duration::from_millis(500));
+        assert_eq!(triggers.inputs(), vec!["path", "event"]);
+        let triggers: Triggers = serde_yaml::from_str("watch:\n  paths: [\"[a\"]\n").unwrap();
+        assert!(triggers.validate().is_err());
+    }
+}
@@ -1,4 +1,6 @@
-use serde::Serializer;
+use anyhow::{anyhow, Result};
+use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+use std::time::Duration;
 
 // Serde crate enforces following signature.
 #[allow(clippy::trivially_copy_pass_by_ref)]
@@ -8,3 +10,61 @@ where
 {
     serializer.serialize_u64(if *input { 1 } else { 0 })
 }
+
+/// Parses durations such as `500ms`, `1.5s`, `10m` or `2h`; a bare number means seconds.
+pub fn parse_duration(raw: &str) -> Result<Duration> {
+    let raw = raw.trim();
+    let split = raw
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(raw.len());
+    let (number, unit) = raw.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid duration {}.", raw))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => {
+            return Err(anyhow!(
+                "Invalid duration unit in {}; use ms, s, m or h.",
+                raw
+            ))
+        }
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| anyhow!("Duration {} is too long.", raw))
+}
+
+pub fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = String::deserialize(deserializer)?;
+    parse_duration(&raw).map_err(D::Error::custom)
+}
+
+pub fn deserialize_optional_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_duration(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("10").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("1 h").unwrap(), Duration::from_secs(3600));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("3d").is_err());
+        let error = parse_duration(&format!("{}h", "9".repeat(400))).unwrap_err();
+        assert!(error.to_string().ends_with("is too long."), "{}", error);
+    }
+}
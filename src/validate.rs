@@ -0,0 +1,493 @@
+use crate::condition::Condition;
+use crate::dag;
+use crate::include;
+use crate::location::Locations;
+use crate::parser::{Namespace, Template};
+use crate::secret;
+use crate::trigger::{self, Triggers, Watch};
+use crate::{compile_var, Config, Step, Workflow, WorkflowConfig, ERROR_FIELDS, WORKFLOWS};
+use std::{collections::HashMap, fmt, path::Path};
+
+/// Something wrong with a configuration, found without running it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Checks a configuration read from `path` and reports every problem it finds, in document
+/// order. Problems in included steps are reported at their `uses` entry.
+pub fn validate(source: &str, path: &Path) -> Vec<Problem> {
+    let expanded = match include::expand(source, path) {
+        Ok(expanded) => expanded,
+        Err(error) => {
+            return vec![Problem {
+                line: None,
+                message: format!("{:#}", error),
+            }]
+        }
+    };
+    let config: Config = match include::deserialize(source, expanded.value) {
+        Ok(config) => config,
+        Err(error) => {
+            return vec![Problem {
+                line: error.location().map(|location| location.line()),
+                message: error.to_string(),
+            }]
+        }
+    };
+    let mut locations = Locations::parse(source).unwrap_or_default();
+    for (from, to) in expanded.aliases {
+        locations.alias(from, to);
+    }
+    let mut validator = Validator {
+        locations,
+        problems: Vec::new(),
+        secrets: config.secrets.keys().cloned().collect(),
+    };
+    if let Err(error) = config.compile_timezone() {
+        validator.report("timezone", format!("{:#}", error));
+    }
+    if let Err(error) = config.job_names() {
+        validator.report("", format!("{:#}", error));
+    }
+    validator.vars("vars", &config.vars);
+    if let Err(error) = secret::check(&config.secrets, config.secret_store.as_deref()) {
+        validator.report("secrets", format!("{:#}", error));
+    }
+    if let Some(triggers) = &config.triggers {
+        validator.triggers("triggers", "", triggers);
+    }
+    if config.jobs.is_empty() {
+        let first = config.triggers.as_ref().map(Triggers::inputs);
+        let first = first.as_deref().unwrap_or_default();
+        validator.steps("workflows", "step", &config.workflows, false, first);
+    }
+    let (section, label) = ("on_failure", "on_failure step");
+    validator.steps(section, label, &config.on_failure, true, &ERROR_FIELDS);
+    for (name, job) in &config.jobs {
+        let path = format!("jobs.{}", name);
+        validator.vars(&format!("{}.vars", path), &job.vars);
+        let label = format!("job {}: ", name);
+        validator.triggers(&format!("{}.triggers", path), &label, &job.triggers);
+        let label = format!("job {}, step", name);
+        let first = job.triggers.inputs();
+        validator.steps(
+            &format!("{}.steps", path),
+            &label,
+            &job.steps,
+            false,
+            &first,
+        );
+        if let Some(on_failure) = &job.on_failure {
+            let label = format!("job {}, on_failure step", name);
+            let section = format!("{}.on_failure", path);
+            validator.steps(&section, &label, on_failure, true, &ERROR_FIELDS);
+        }
+    }
+    validator.problems.sort_by_key(|problem| problem.line);
+    validator.problems
+}
+
+struct Validator {
+    locations: Locations,
+    problems: Vec<Problem>,
+    /// The names declared under `secrets`.
+    secrets: Vec<String>,
+}
+
+impl Validator {
+    fn report(&mut self, path: &str, message: String) {
+        self.problems.push(Problem {
+            line: self.locations.line(path),
+            message,
+        });
+    }
+
+    fn vars(&mut self, section: &str, vars: &HashMap<String, String>) {
+        let mut vars: Vec<_> = vars.iter().collect();
+        vars.sort();
+        for (name, value) in vars {
+            if let Err(error) = compile_var(name, value) {
+                self.report(&format!("{}.{}", section, name), format!("{:#}", error));
+            }
+        }
+    }
+
+    /// Checks the schedule and its timezone, prefixing messages with `label`.
+    fn triggers(&mut self, section: &str, label: &str, triggers: &Triggers) {
+        if let Some(schedule) = &triggers.schedule {
+            if let Err(error) = trigger::parse_schedule(schedule) {
+                let message = format!("{}{:#}", label, error);
+                self.report(&format!("{}.schedule", section), message);
+            }
+        }
+        if let Err(error) = triggers.compile_timezone() {
+            let message = format!("{}{:#}", label, error);
+            self.report(&format!("{}.timezone", section), message);
+        }
+        if let Some(webhook) = &triggers.webhook {
+            let path = format!("{}.webhook", section);
+            match webhook.validate().and_then(|_| webhook.compile_secret()) {
+                Ok(Some(secret)) => {
+                    let name = format!("{}webhook secret", label);
+                    self.declared(&path, &name, &secret.variables());
+                }
+                Ok(None) => {}
+                Err(error) => self.report(&path, format!("{}{:#}", label, error)),
+            }
+        }
+        if let Some(Err(error)) = triggers.watch.as_ref().map(Watch::validate) {
+            let message = format!("{}{:#}", label, error);
+            self.report(&format!("{}.watch", section), message);
+        }
+    }
+
+    /// Checks the steps listed at `section`, naming each as `<label> <n> (<type>)`. The
+    /// first step to run is given `first` as its inputs.
+    fn steps(
+        &mut self,
+        section: &str,
+        label: &str,
+        workflows: &[WorkflowConfig],
+        handler: bool,
+        first: &[&str],
+    ) {
+        let mut needs_ok = true;
+        for index in 0..workflows.len() {
+            if let Err(error) = dag::dependencies(workflows, index) {
+                self.report(
+                    &format!("{}[{}].needs", section, index),
+                    format!("{:#}", error),
+                );
+                needs_ok = false;
+            }
+        }
+        // Steps are checked in the order they run, or as written when `needs` is broken.
+        let order = match dag::order(workflows) {
+            Ok(order) => order,
+            Err(error) => {
+                if needs_ok {
+                    self.report(section, format!("{:#}", error));
+                }
+                (0..workflows.len()).collect()
+            }
+        };
+        let mut compiled: Vec<Step> = Vec::new();
+        // The outputs of each step by id, unless an unknown type or a step whose outputs
+        // depend on the run hides them.
+        let mut recorded: HashMap<&str, Option<Vec<&str>>> = HashMap::new();
+        // The same by the index each step is written at.
+        let mut emitted: HashMap<usize, Option<Vec<&str>>> = HashMap::new();
+        for index in order {
+            let config = &workflows[index];
+            let path = format!("{}[{}]", section, index);
+            let name = format!("{} {} ({})", label, index + 1, config.workflow_type);
+            // What `input` holds: the joined outputs of `needs`, or else those of the step
+            // written before this one; steps that need nothing read the pipeline's input.
+            let inputs = match (&config.needs, index) {
+                (Some(needs), _) if needs.is_empty() => Some(first.to_vec()),
+                (None, 0) => Some(first.to_vec()),
+                (Some(needs), _) => needs.iter().try_fold(Vec::new(), |mut joined, id| {
+                    joined.extend(recorded.get(&id[..])?.as_deref()?);
+                    Some(joined)
+                }),
+                (None, _) => emitted.get(&(index - 1)).cloned().flatten(),
+            };
+            let found = self.problems.len();
+            self.step(&path, &name, config, inputs.as_deref());
+            let outputs = WORKFLOWS
+                .get(&config.workflow_type.to_lowercase()[..])
+                .and_then(|workflow| workflow.step_outputs(&config.parameters).ok().flatten());
+            if let Some(id) = &config.id {
+                recorded.insert(id, outputs.clone());
+            }
+            emitted.insert(index, outputs);
+            if self.problems.len() > found {
+                continue;
+            }
+            // Ids, `steps.*` and `error.*` references and retry settings are checked by
+            // compiling the step as a run would.
+            let dependencies = dag::dependencies(workflows, index).unwrap_or_default();
+            match config.compile(index, dependencies, &compiled, handler) {
+                Ok(step) => compiled.push(step),
+                Err(error) => self.report(&path, format!("{:#}", error)),
+            }
+        }
+    }
+
+    fn step(&mut self, path: &str, name: &str, config: &WorkflowConfig, inputs: Option<&[&str]>) {
+        let workflow = WORKFLOWS.get(&config.workflow_type.to_lowercase()[..]);
+        match workflow {
+            Some(workflow) => {
+                let accepted: Vec<_> = workflow.parameters().iter().map(|s| s.name).collect();
+                let mut keys: Vec<_> = config.parameters.keys().collect();
+                keys.sort();
+                for key in keys {
+                    if !accepted.contains(&&key[..]) {
+                        self.report(
+                            &format!("{}.parameters.{}", path, key),
+                            format!(
+                                "{}: unknown parameter `{}`; it accepts {:?}.",
+                                name, key, accepted
+                            ),
+                        );
+                    }
+                }
+                for spec in workflow.parameters() {
+                    if spec.required && !config.parameters.contains_key(spec.name) {
+                        self.report(
+                            &format!("{}.parameters", path),
+                            format!("{}: missing required parameter `{}`.", name, spec.name),
+                        );
+                    }
+                }
+            }
+            None => {
+                let mut known: Vec<_> = WORKFLOWS.keys().collect();
+                known.sort();
+                self.report(
+                    &format!("{}.type", path),
+                    format!(
+                        "{}: unknown type `{}`; use one of {:?}.",
+                        name, config.workflow_type, known
+                    ),
+                );
+            }
+        }
+
+        let mut parameters: Vec<_> = config.parameters.iter().collect();
+        parameters.sort_by_key(|(key, _)| *key);
+        for (key, value) in parameters {
+            let path = format!("{}.parameters.{}", path, key);
+            let name = format!("{}, parameter `{}`", name, key);
+            for raw in value.items() {
+                match Template::parse(raw) {
+                    Ok(template) => {
+                        let variables = template.variables();
+                        self.inputs(&path, &name, &variables, inputs);
+                        self.declared(&path, &name, &variables);
+                    }
+                    Err(error) => self.report(&path, format!("{}: {}.", name, error)),
+                }
+            }
+        }
+        if let Some(raw) = &config.condition {
+            let path = format!("{}.if", path);
+            let name = format!("{}, if", name);
+            match Condition::parse(raw) {
+                Ok(condition) => {
+                    let variables = condition.variables();
+                    self.inputs(&path, &name, &variables, inputs);
+                    self.declared(&path, &name, &variables);
+                }
+                Err(error) => self.report(&path, format!("{}: {}.", name, error)),
+            }
+        }
+    }
+
+    /// Reports every `secrets.<name>` that the configuration does not declare.
+    fn declared(&mut self, path: &str, name: &str, variables: &[(Namespace, &str)]) {
+        for (namespace, field) in variables {
+            if *namespace == Namespace::Secrets && !self.secrets.iter().any(|s| s == field) {
+                self.report(
+                    path,
+                    format!("{}: secrets.{} is not declared under secrets.", name, field),
+                );
+            }
+        }
+    }
+
+    /// Reports every `input.<field>` that the previous step does not provide.
+    fn inputs(
+        &mut self,
+        path: &str,
+        name: &str,
+        variables: &[(Namespace, &str)],
+        inputs: Option<&[&str]>,
+    ) {
+        let inputs = match inputs {
+            Some(inputs) => inputs,
+            None => return,
+        };
+        for (namespace, field) in variables {
+            if *namespace == Namespace::Input && !inputs.contains(field) {
+                self.report(
+                    path,
+                    format!(
+                        "{}: input.{} is not provided by the previous step, which outputs {:?}.",
+                        name, field, inputs
+                    ),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stand_in::TempDir;
+
+    #[test]
+    fn test_validate() {
+        let source = r#"
+timezone: Mars/Olympus
+workflows:
+  - type: http
+    parameters:
+      method: GET
+      methd: GET
+  - type: ech
+    parameters:
+      text: "{input.status_code"
+  - type: echo
+    parameters:
+      text: "{input.status_code}"
+  - type: rss
+    parameters:
+      text: "{input.text}"
+on_failure:
+  - type: echo
+    parameters:
+      text: "{error.message} {input.text}"
+"#;
+        let problems: Vec<_> = validate(source, Path::new("test.yml"))
+            .into_iter()
+            .map(|problem| (problem.line, problem.message))
+            .collect();
+        let lines: Vec<_> = problems.iter().map(|(line, _)| *line).collect();
+        assert_eq!(
+            lines,
+            vec![
+                Some(2),
+                Some(5),
+                Some(7),
+                Some(8),
+                Some(10),
+                Some(16),
+                Some(20)
+            ],
+            "{:#?}",
+            problems
+        );
+        assert!(problems[1].1.contains("missing required parameter `url`"));
+        assert!(problems[2].1.contains("unknown parameter `methd`"));
+        assert!(problems[3].1.contains("unknown type `ech`"));
+        assert!(problems[4].1.contains("expected"));
+        assert!(problems[5].1.contains("input.text is not provided"));
+        assert!(problems[6].1.contains("input.text is not provided"));
+
+        let source = "workflows:\n  - type: echo\n    parameters:\n      text: hi\n";
+        assert_eq!(validate(source, Path::new("test.yml")), vec![]);
+        let source = r#"
+jobs:
+  xbox:
+    triggers:
+      schedule: every day
+    steps:
+      - type: echo
+        parameters:
+          text: "{vars.x}"
+  github:
+    vars:
+      x: "{input.y}"
+    steps:
+      - type: echo
+        parameters:
+          text: hi
+"#;
+        let lines: Vec<_> = validate(source, Path::new("test.yml"))
+            .into_iter()
+            .map(|p| p.line)
+            .collect();
+        assert_eq!(lines, vec![Some(5), Some(12)]);
+
+        let source = r#"
+workflows:
+  - type: echo
+    needs: [page, feed]
+    parameters:
+      text: "{input.status_code} {input.title} {input.size}"
+  - id: page
+    type: http
+    needs: []
+    parameters:
+      url: https://example.com
+  - id: feed
+    type: rss
+    needs: [paeg]
+    parameters:
+      text: "{input.text}"
+"#;
+        let problems = validate(source, Path::new("test.yml"));
+        assert_eq!(problems.len(), 1, "{:#?}", problems);
+        assert_eq!(problems[0].line, Some(14));
+        assert!(problems[0].message.contains("needs `paeg`"));
+        let source = source.replace("paeg", "page");
+        let problems = validate(&source, Path::new("test.yml"));
+        assert_eq!(problems.len(), 1, "{:#?}", problems);
+        assert_eq!(problems[0].line, Some(6));
+        assert!(problems[0].message.contains("input.size is not provided"));
+
+        let source = r#"
+jobs:
+  github:
+    triggers:
+      webhook:
+        path: github
+        secret: "{vars.key}"
+    steps:
+      - type: echo
+        parameters:
+          text: "{input.body} {input.headers}"
+"#;
+        let problems = validate(source, Path::new("test.yml"));
+        assert_eq!(problems.len(), 1, "{:#?}", problems);
+        assert_eq!(problems[0].line, Some(5));
+        assert!(problems[0].message.contains("should start with /"));
+
+        let source = r#"
+secrets:
+  token:
+    env: GIST_TOKEN
+  key:
+    store: github_key
+vars:
+  auth: "{secrets.token}"
+workflows:
+  - type: echo
+    parameters:
+      text: "{secrets.token} {secrets.tokn}"
+"#;
+        let problems: Vec<_> = validate(source, Path::new("test.yml"))
+            .into_iter()
+            .map(|problem| (problem.line, problem.message))
+            .collect();
+        let lines: Vec<_> = problems.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![Some(2), Some(8), Some(12)], "{:#?}", problems);
+        assert!(problems[0].1.contains("no secret_store"));
+        assert!(problems[1].1.contains("can only use env"));
+        assert!(problems[2].1.contains("secrets.tokn is not declared"));
+
+        let source = "workflows:\n  - type: echo\n    parameters: 3\n";
+        assert_eq!(validate(source, Path::new("test.yml"))[0].line, Some(3));
+
+        let dir = TempDir::new("validate");
+        std::fs::write(dir.join("fetch.yml"), "steps:\n  - type: http\n").unwrap();
+        let source =
+            "workflows:\n  - uses: fetch.yml\n  - type: echo\n    parameters:\n      tex: hi\n";
+        let problems = validate(source, &dir.join("main.yml"));
+        let lines: Vec<_> = problems.iter().map(|p| p.line).collect();
+        assert_eq!(lines, vec![Some(2), Some(4), Some(5)], "{:#?}", problems);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::trigger::Watch;
+use anyhow::{Context as _, Result};
+use glob::{MatchOptions, Pattern};
+use notify::{
+    event::ModifyKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _,
+};
+use std::{
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tracing::warn;
+
+/// What the first step of a job started by a change finds in `input`: the changed path,
+/// and whether it was `created`, `modified`, `renamed` or `removed`.
+pub const FIELDS: [&str; 2] = ["path", "event"];
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Reports changes to the files a `watch` trigger names.
+pub struct Watcher {
+    patterns: Vec<Pattern>,
+    debounce: Duration,
+    events: UnboundedReceiver<notify::Result<Event>>,
+    /// Stops watching when dropped.
+    _watcher: RecommendedWatcher,
+}
+
+impl Watcher {
+    /// Watches the paths of `watch`, relative to the directory `base`.
+    pub fn new(watch: &Watch, base: &Path) -> Result<Self> {
+        let (sender, events) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver is gone only once the watcher is being dropped.
+            let _ = sender.send(event);
+        })?;
+        let mut patterns = Vec::new();
+        for raw in &watch.paths {
+            let path = base.join(raw);
+            let (root, mode) = root(&path);
+            watcher
+                .watch(&root, mode)
+                .with_context(|| format!("Unable to watch {}.", root.display()))?;
+            let pattern = Pattern::new(&path.to_string_lossy())
+                .with_context(|| format!("Invalid watch path `{}`.", raw))?;
+            patterns.push(pattern);
+        }
+        Ok(Watcher {
+            patterns,
+            debounce: watch.debounce,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Waits for a change, then until none has come for the debounce period, and returns
+    /// each changed path once with what happened to it.
+    pub async fn changes(&mut self) -> Option<Vec<(PathBuf, &'static str)>> {
+        let mut changes = Vec::new();
+        while changes.is_empty() {
+            let event = self.events.recv().await?;
+            self.collect(event, &mut changes);
+        }
+        while let Ok(Some(event)) = tokio::time::timeout(self.debounce, self.events.recv()).await {
+            self.collect(event, &mut changes);
+        }
+        Some(changes)
+    }
+
+    fn collect(&self, event: notify::Result<Event>, changes: &mut Vec<(PathBuf, &'static str)>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(error) => {
+                warn!("Unable to watch: {}", error);
+                return;
+            }
+        };
+        let kind = match event.kind {
+            EventKind::Create(_) => "created",
+            EventKind::Modify(ModifyKind::Name(_)) => "renamed",
+            EventKind::Modify(_) => "modified",
+            EventKind::Remove(_) => "removed",
+            _ => return,
+        };
+        for path in event.paths {
+            let watched = self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.matches_path_with(&path, MATCH_OPTIONS));
+            if !watched {
+                continue;
+            }
+            match changes.iter_mut().find(|(changed, _)| *changed == path) {
+                // A file written right after it is created is still new.
+                Some((_, "created")) if kind == "modified" => {}
+                Some((_, last)) => *last = kind,
+                None => changes.push((path, kind)),
+            }
+        }
+    }
+}
+
+/// The directory to watch for a path or pattern, and whether to watch beneath it.
+fn root(pattern: &Path) -> (PathBuf, RecursiveMode) {
+    let components: Vec<Component> = pattern.components().collect();
+    let wildcard = components.iter().position(|component| {
+        let text = component.as_os_str().to_string_lossy();
+        text.contains(['*', '?', '['])
+    });
+    match wildcard {
+        // Editors often replace a file rather than write it, so its directory is watched.
+        None => {
+            let parent = pattern.parent().unwrap_or(pattern);
+            (parent.to_path_buf(), RecursiveMode::NonRecursive)
+        }
+        Some(index) => {
+            let root: PathBuf = components[..index].iter().collect();
+            let nested = index + 1 < components.len()
+                || components[index]
+                    .as_os_str()
+                    .to_string_lossy()
+                    .contains("**");
+            let mode = if nested {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            (root, mode)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stand_in::TempDir;
+    use crate::RUNTIME;
+
+    #[test]
+    fn test_root() {
+        assert_eq!(
+            root(Path::new("/a/b.yml")),
+            (PathBuf::from("/a"), RecursiveMode::NonRecursive)
+        );
+        assert_eq!(
+            root(Path::new("/a/*.md")),
+            (PathBuf::from("/a"), RecursiveMode::NonRecursive)
+        );
+        assert_eq!(
+            root(Path::new("/a/**/*.md")),
+            (PathBuf::from("/a"), RecursiveMode::Recursive)
+        );
+    }
+
+    #[test]
+    fn test_changes() {
+        let dir = TempDir::new("watch");
+        let watch = Watch {
+            paths: vec!["*.txt".to_string()],
+            debounce: Duration::from_millis(200),
+        };
+        let changes = RUNTIME.block_on(async {
+            let mut watcher = Watcher::new(&watch, &dir).unwrap();
+            std::fs::write(dir.join("a.txt"), "1").unwrap();
+            std::fs::write(dir.join("b.md"), "1").unwrap();
+            std::fs::write(dir.join("a.txt"), "2").unwrap();
+            tokio::time::timeout(Duration::from_secs(5), watcher.changes()).await
+        });
+        assert_eq!(
+            changes.unwrap().unwrap(),
+            vec![(dir.join("a.txt"), "created")]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use crate::{load_config, secret, Context, Outputs, Pipeline};
+use anyhow::{anyhow, bail, Context as _, Result};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Map, Value};
+use sha2::Sha256;
+use std::{
+    io::{self, Read},
+    path::Path,
+    sync::Arc,
+    time::Instant,
+};
+use tiny_http::{Header, Request, Response, Server};
+use tracing::{debug, error, info};
+
+/// Where `workflows serve` listens unless `--listen` says otherwise.
+pub const ADDRESS: &str = "127.0.0.1:8080";
+/// What the first step of a job started by a webhook finds in `input`; `query` and
+/// `headers` are JSON objects, with header names in lowercase.
+pub const FIELDS: [&str; 5] = ["method", "path", "query", "headers", "body"];
+const SIGNATURE_HEADER: &str = "X-Hub-Signature-256";
+/// The largest body read, as much as GitHub sends.
+const MAX_BODY: u64 = 25 * 1024 * 1024;
+
+/// What a handler needs of a request, read on a blocking thread.
+struct Incoming {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: io::Result<Vec<u8>>,
+}
+
+impl Incoming {
+    fn read(request: &mut Request) -> Self {
+        let headers = request
+            .headers()
+            .iter()
+            .map(|header| (header.field.to_string(), header.value.to_string()))
+            .collect();
+        let mut body = Vec::new();
+        let read = request
+            .as_reader()
+            .take(MAX_BODY + 1)
+            .read_to_end(&mut body)
+            .and_then(|read| match read as u64 > MAX_BODY {
+                true => Err(io::Error::new(
+                    io::ErrorKind::FileTooLarge,
+                    format!("it is larger than {} bytes", MAX_BODY),
+                )),
+                false => Ok(body),
+            });
+        Incoming {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers,
+            body: read,
+        }
+    }
+}
+
+/// A job with a `webhook` trigger.
+struct Route {
+    /// `<config> job <name>`, as shown in messages.
+    name: String,
+    job: String,
+    path: String,
+    secret: Option<String>,
+    pipeline: Pipeline,
+}
+
+/// Loads the jobs with a webhook from every configuration.
+fn routes(paths: &[String]) -> Result<Vec<Route>> {
+    let mut routes: Vec<Route> = Vec::new();
+    for path in paths {
+        let config = load_config(Path::new(path))?;
+        for job in config.job_names()? {
+            let mut pipeline = config
+                .compile(job)
+                .with_context(|| format!("Invalid job {} in {}.", job, path))?;
+            let webhook = match pipeline.triggers.webhook.clone() {
+                Some(webhook) => webhook,
+                None => continue,
+            };
+            let name = format!("{} job {}", path, job);
+            if let Some(other) = routes.iter().find(|route| route.path == webhook.path) {
+                bail!("Both {} and {} serve {}.", other.name, name, webhook.path);
+            }
+            pipeline.path = Some(Path::new(path).canonicalize()?);
+            let secret = match webhook.compile_secret()? {
+                Some(template) => Some(
+                    template
+                        .render(&Outputs::new(), &Context::new(&pipeline)?)
+                        .with_context(|| format!("Unable to render the secret of {}.", name))?,
+                ),
+                None => None,
+            };
+            routes.push(Route {
+                name,
+                job: job.to_string(),
+                path: webhook.path,
+                secret,
+                pipeline,
+            });
+        }
+    }
+    if routes.is_empty() {
+        bail!("No job in {} has a webhook.", paths.join(", "));
+    }
+    Ok(routes)
+}
+
+/// Serves the webhooks of every configuration on `address` until stopped.
+pub async fn serve(paths: &[String], address: &str) -> Result<()> {
+    let routes = routes(paths)?;
+    let server = Server::http(address)
+        .map_err(|error| anyhow!("Unable to listen on {}: {}", address, error))?;
+    for route in &routes {
+        info!("http://{}{} runs {}.", address, route.path, route.name);
+    }
+    listen(server, routes).await
+}
+
+/// Accepts requests until stopped; each one is read and answered on its own task, and a
+/// request that fails to arrive is logged and skipped.
+async fn listen(server: Server, routes: Vec<Route>) -> Result<()> {
+    let server = Arc::new(server);
+    let routes = Arc::new(routes);
+    loop {
+        let server = server.clone();
+        match tokio::task::spawn_blocking(move || server.recv()).await? {
+            Ok(request) => {
+                tokio::spawn(respond(request, routes.clone()));
+            }
+            Err(error) => error!("Unable to receive a request: {}", error),
+        }
+    }
+}
+
+async fn respond(mut request: Request, routes: Arc<Vec<Route>>) {
+    let read = tokio::task::spawn_blocking(move || {
+        let incoming = Incoming::read(&mut request);
+        (request, incoming)
+    });
+    let (request, incoming) = match read.await {
+        Ok(read) => read,
+        Err(error) => return error!("Unable to read a request: {}", error),
+    };
+    let (status, summary) = match &incoming.body {
+        Ok(body) => answer(&incoming, body, &routes).await,
+        Err(error) => (
+            match error.kind() {
+                io::ErrorKind::FileTooLarge => 413,
+                _ => 400,
+            },
+            json!({ "error": format!("Unable to read the body: {}", error) }),
+        ),
+    };
+    info!("{} {} answered {}.", incoming.method, incoming.url, status);
+    // Outputs and errors may hold secrets the caller should not see.
+    let summary = secret::mask(&summary.to_string()).into_owned();
+    debug!(%summary, "Run summary.");
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("The header is valid.");
+    let response = Response::from_string(summary)
+        .with_status_code(status)
+        .with_header(content_type);
+    if let Err(error) = request.respond(response) {
+        error!("Unable to respond: {}", error);
+    }
+}
+
+/// Runs the job the request is routed to and summarises the run.
+async fn answer(request: &Incoming, body: &[u8], routes: &[Route]) -> (u16, Value) {
+    let url = &request.url[..];
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let route = match routes.iter().find(|route| route.path == path) {
+        Some(route) => route,
+        None => return (404, json!({ "error": format!("No job serves {}.", path) })),
+    };
+    if let Some(secret) = &route.secret {
+        let signature = request
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(SIGNATURE_HEADER))
+            .map(|(_, value)| &value[..]);
+        if let Err(error) = verify(secret, body, signature) {
+            return (
+                401,
+                json!({ "job": route.job, "error": format!("{:#}", error) }),
+            );
+        }
+    }
+
+    let mut headers = Map::new();
+    for (name, value) in &request.headers {
+        let name = name.to_ascii_lowercase();
+        match headers.get_mut(&name) {
+            Some(Value::String(joined)) => {
+                joined.push_str(", ");
+                joined.push_str(value);
+            }
+            _ => {
+                headers.insert(name, value.clone().into());
+            }
+        }
+    }
+    let query: Map<_, _> = url::form_urlencoded::parse(query.as_bytes())
+        .map(|(name, value)| (name.into_owned(), value.into_owned().into()))
+        .collect();
+    let mut input = Outputs::new();
+    input.insert(FIELDS[0], request.method.clone());
+    input.insert(FIELDS[1], path.to_string());
+    input.insert(FIELDS[2], Value::Object(query).to_string());
+    input.insert(FIELDS[3], Value::Object(headers).to_string());
+    input.insert(FIELDS[4], String::from_utf8_lossy(body).into_owned());
+
+    let started = Instant::now();
+    let result = match Context::new(&route.pipeline) {
+        Ok(context) => route.pipeline.run_with_input(&context, &input).await,
+        Err(error) => Err(error),
+    };
+    let seconds = started.elapsed().as_secs_f64();
+    match result {
+        Ok(outputs) => (
+            200,
+            json!({ "job": route.job, "status": "succeeded", "seconds": seconds, "outputs": outputs }),
+        ),
+        Err(error) => (
+            500,
+            json!({ "job": route.job, "status": "failed", "seconds": seconds, "error": format!("{:#}", error) }),
+        ),
+    }
+}
+
+/// Checks a GitHub style `sha256=<hex>` HMAC of the body.
+fn verify(secret: &str, body: &[u8], signature: Option<&str>) -> Result<()> {
+    let signature =
+        signature.with_context(|| format!("The request has no {} header.", SIGNATURE_HEADER))?;
+    let digest = signature
+        .strip_prefix("sha256=")
+        .with_context(|| format!("{} should start with sha256=.", SIGNATURE_HEADER))?;
+    let digest =
+        hex::decode(digest).with_context(|| format!("{} is not hexadecimal.", SIGNATURE_HEADER))?;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size.");
+    mac.update(body);
+    mac.verify_slice(&digest)
+        .map_err(|_| anyhow!("The signature does not match."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stand_in::TempDir;
+    use crate::{CLIENT, RUNTIME};
+
+    fn sign(secret: &str, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn test_serve() {
+        let dir = TempDir::new("webhook");
+        let config = dir.join("hooks.yml");
+        let note = dir.join("note.txt");
+        std::fs::write(
+            &config,
+            format!(
+                r#"
+jobs:
+  github:
+    triggers:
+      webhook:
+        path: /github
+        secret: s3cret
+    steps:
+      - type: save
+        parameters:
+          text: "{{input.method}} {{input.body}} {{input.query}}"
+          destination: {0}
+      - type: read
+        parameters:
+          path: {0}
+  nightly:
+    steps:
+      - type: echo
+        parameters:
+          text: hi
+"#,
+                note.display()
+            ),
+        )
+        .unwrap();
+        let routes = routes(&[config.display().to_string()]).unwrap();
+        assert_eq!(routes.len(), 1);
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+        RUNTIME.spawn(listen(server, routes));
+
+        let send = |path: &str, signature: &str, body: Vec<u8>| {
+            let request = CLIENT
+                .post(format!("{}{}", url, path))
+                .header(SIGNATURE_HEADER, signature)
+                .body(body);
+            RUNTIME.block_on(async {
+                let response = request.send().await.unwrap();
+                let status = response.status().as_u16();
+                (status, response.json::<Value>().await.unwrap())
+            })
+        };
+        let post = |path: &str, signature: &str| send(path, signature, b"push".to_vec());
+        let (status, summary) = post("/github?ref=main", &sign("s3cret", "push"));
+        assert_eq!(status, 200, "{}", summary);
+        assert_eq!(summary["status"], "succeeded");
+        assert_eq!(summary["outputs"][0]["text"], r#"POST push {"ref":"main"}"#);
+        let (status, summary) = post("/github", &sign("guess", "push"));
+        assert_eq!(status, 401);
+        assert_eq!(summary["error"], "The signature does not match.");
+        let (status, _) = post("/nightly", "");
+        assert_eq!(status, 404);
+        let (status, summary) = send("/github", "", vec![b'x'; MAX_BODY as usize + 1]);
+        assert_eq!(status, 413, "{}", summary);
+    }
+}
@@ -1,10 +1,12 @@
-use crate::{Context, Input, Inputs, Workflow};
+use crate::parameter::{OutputSpec, ParameterSpec, ParameterType};
+use crate::{Context, Input, Inputs, Outputs, Workflow};
 
 use anyhow::Result;
-use reqwest::blocking::Client;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct WeChatAccessToken {
     #[serde(rename = "errcode")]
@@ -36,6 +38,7 @@ struct WeChatMessageText<'a> {
     content: &'a str,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 struct WeChatSendResponse {
     #[serde(rename = "errcode")]
@@ -47,44 +50,79 @@ struct WeChatSendResponse {
 pub struct WeChat {}
 
 impl WeChat {
+    const DESCRIPTION: &'static str = "Sends a text message through a WeChat Work application.";
+
     // Input
     const CORP_ID: &'static str = "corp_id";
     const CORP_SECRET: &'static str = "secret";
     const AGENT_ID: &'static str = "agent_id";
     const TEXT: &'static str = "text";
-    const PARAMS: [&'static str; 4] = [
-        WeChat::CORP_ID,
-        WeChat::CORP_SECRET,
-        WeChat::AGENT_ID,
-        WeChat::TEXT,
+    const BASE_URL: &'static str = "base_url";
+    const PARAMS: [ParameterSpec; 5] = [
+        ParameterSpec::new(
+            WeChat::CORP_ID,
+            ParameterType::String,
+            "WeChat Work corporation id.",
+        )
+        .required(),
+        ParameterSpec::new(
+            WeChat::CORP_SECRET,
+            ParameterType::String,
+            "Secret of the application.",
+        )
+        .required()
+        .secret(),
+        ParameterSpec::new(
+            WeChat::AGENT_ID,
+            ParameterType::Int,
+            "Id of the application.",
+        )
+        .required(),
+        ParameterSpec::new(
+            WeChat::TEXT,
+            ParameterType::String,
+            "Message to send to everyone.",
+        )
+        .required(),
+        ParameterSpec::new(
+            WeChat::BASE_URL,
+            ParameterType::String,
+            "Root of the WeChat Work API.",
+        )
+        .default("https://qyapi.weixin.qq.com"),
     ];
 
     // Output
     const ERROR_CODE: &'static str = "error_code";
-    const OUTPUT: [&'static str; 0] = [];
+    const OUTPUT: [OutputSpec; 1] = [OutputSpec::new(
+        WeChat::ERROR_CODE,
+        "`errcode` of the reply; 0 on success.",
+    )];
 }
 
+#[async_trait]
 impl Workflow for WeChat {
-    fn execute(&self, context: &mut Context, input: Inputs) -> Result<()> {
+    async fn execute(&self, context: &Context, input: Inputs) -> Result<Vec<Outputs>> {
         let corp_id = input.parameter(WeChat::CORP_ID);
         let secret = input.parameter(WeChat::CORP_SECRET);
-        let agent_id = input.parameter(WeChat::AGENT_ID).parse()?;
+        let agent_id = input.int(WeChat::AGENT_ID).unwrap_or_default();
         let text = input.parameter(WeChat::TEXT);
+        let base_url = input.parameter(WeChat::BASE_URL).trim_end_matches('/');
 
-        let client = Client::new();
+        let client = context.http_client();
 
         let url = format!(
-            "https://qyapi.weixin.qq.com/cgi-bin/gettoken?corpid={}&corpsecret={}",
-            corp_id, secret
+            "{}/cgi-bin/gettoken?corpid={}&corpsecret={}",
+            base_url, corp_id, secret
         );
 
-        let response = client.get(&url).send()?;
-        let token: WeChatAccessToken = response.json()?;
+        let response = client.get(&url).send().await?;
+        let token: WeChatAccessToken = response.json().await?;
 
         let message = WeChatMessage {
             to_user: "@all",
             to_party: None,
-            agent_id: agent_id,
+            agent_id,
             message_type: "text",
             text: WeChatMessageText { content: text },
             enable_duplicate_check: false,
@@ -92,24 +130,30 @@ impl Workflow for WeChat {
         };
 
         let url = format!(
-            "https://qyapi.weixin.qq.com/cgi-bin/message/send?access_token={}",
-            token.access_token
+            "{}/cgi-bin/message/send?access_token={}",
+            base_url, token.access_token
         );
-        let response: WeChatSendResponse = client.post(&url).json(&message).send()?.json()?;
+        let response: WeChatSendResponse = client
+            .post(&url)
+            .json(&message)
+            .send()
+            .await?
+            .json()
+            .await?;
 
         let mut result = HashMap::new();
         result.insert(WeChat::ERROR_CODE, response.error_code.to_string());
 
-        if let Some(next) = context.next() {
-            next.execute(context, result)?;
-        }
-        Ok(())
+        Ok(vec![result])
     }
 
-    fn parameters(&self) -> &'static [&'static str] {
-        return &WeChat::PARAMS;
+    fn description(&self) -> &'static str {
+        WeChat::DESCRIPTION
+    }
+    fn parameters(&self) -> &'static [ParameterSpec] {
+        &WeChat::PARAMS
     }
-    fn outputs(&self) -> &'static [&'static str] {
-        return &WeChat::OUTPUT;
+    fn outputs(&self) -> &'static [OutputSpec] {
+        &WeChat::OUTPUT
     }
 }